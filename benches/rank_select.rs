@@ -0,0 +1,65 @@
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use runaway_datastructures::aligned::Pages;
+use runaway_datastructures::interleaved_vector::InterleavedVector;
+use runaway_datastructures::naive_vector::NaiveVector;
+use runaway_datastructures::rank9::{Rank9, SimpleSelect};
+use runaway_datastructures::rank_select::RankSelect;
+use runaway_datastructures::runaway_vector::RunawayVector;
+
+const LEN: usize = 1 << 24;
+const QUERIES: usize = 1000;
+
+// Compares all vectors on the same bits and the same queries.
+fn rank_select(c: &mut Criterion) {
+    let mut rng = SmallRng::seed_from_u64(0);
+    for density in [0.05, 0.5] {
+        let bit_vec: BitVec<u64, Lsb0> = (0..LEN).map(|_| rng.gen_bool(density)).collect();
+        let vectors: [(&str, Box<dyn RankSelect>); 5] = [
+            ("runaway", Box::new(RunawayVector::new(&bit_vec))),
+            ("naive", Box::new(NaiveVector::new(&bit_vec, 512))),
+            ("rank9", Box::new(Rank9::new(&bit_vec))),
+            ("simple_select", Box::new(SimpleSelect::new(&bit_vec))),
+            (
+                "interleaved",
+                Box::new(InterleavedVector::new(&bit_vec, Pages::Huge)),
+            ),
+        ];
+        let ones = bit_vec.count_ones();
+        let positions: Vec<usize> = (0..QUERIES).map(|_| rng.gen_range(0..LEN)).collect();
+        let ranks: Vec<usize> = (0..QUERIES).map(|_| rng.gen_range(1..=ones)).collect();
+
+        let mut group = c.benchmark_group(format!("density {density}"));
+        for (name, vector) in &vectors {
+            println!(
+                "{name} at density {density}: {} bits of support",
+                vector.space_usage()
+            );
+            group.bench_with_input(
+                BenchmarkId::new("rank1", name),
+                &positions,
+                |b, positions| {
+                    b.iter(|| {
+                        for idx in positions {
+                            black_box(vector.rank1(*idx));
+                        }
+                    })
+                },
+            );
+            group.bench_with_input(BenchmarkId::new("select1", name), &ranks, |b, ranks| {
+                b.iter(|| {
+                    for rank in ranks {
+                        black_box(vector.select1(*rank));
+                    }
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, rank_select);
+criterion_main!(benches);
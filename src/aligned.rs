@@ -0,0 +1,165 @@
+//! # AlignedWords
+//!
+//! A buffer of words which starts at a cache line of 64 bytes, so that each block of eight words
+//! lies in a single cache line. With [`Pages::Huge`] the buffer is an anonymous mapping which is
+//! advised with `madvise` to be backed by transparent huge pages on Linux, which saves TLB misses
+//! on random accesses to large vectors. The kernel may ignore the advice, and other systems only
+//! get the mapping, which is aligned to a page as well.
+//!
+//! Other values, like the counters of a rank index, are stored in an [`Aligned`] buffer of their
+//! own type, which is laid out the same way.
+//!
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::{mem, slice};
+
+#[cfg(target_os = "linux")]
+use memmap2::Advice;
+use memmap2::MmapMut;
+
+pub const CACHE_LINE_WORDS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pages {
+    /// The buffer is allocated on the heap.
+    Normal,
+    /// The buffer is mapped and backed by huge pages if the system supports them.
+    Huge,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, align(64))]
+struct CacheLine([u64; CACHE_LINE_WORDS]);
+
+enum Storage {
+    Heap(Vec<CacheLine>),
+    Mapped(MmapMut),
+}
+
+/// Values which can be stored in an [`Aligned`] buffer.
+///
+/// # Safety
+///
+/// A value whose bytes are all zero must be valid, and the alignment of the type must not be larger
+/// than a cache line.
+pub unsafe trait Zeroable: Copy {}
+
+// Safety: Zero is a valid integer and its alignment is 8 bytes.
+unsafe impl Zeroable for u64 {}
+
+pub struct Aligned<T> {
+    storage: Storage,
+    len: usize,
+    values: PhantomData<T>,
+}
+
+pub type AlignedWords = Aligned<u64>;
+
+impl<T: Zeroable> Aligned<T> {
+    /// Allocates `len` values which are all zero. If the mapping for huge pages fails, the values
+    /// are allocated on the heap instead.
+    pub fn zeroed(len: usize, pages: Pages) -> Self {
+        let lines = Self::lines(len);
+        if pages == Pages::Huge && lines > 0 {
+            if let Ok(mmap) = MmapMut::map_anon(lines * mem::size_of::<CacheLine>()) {
+                // The advice is only a hint, so a kernel without huge pages is not an error.
+                #[cfg(target_os = "linux")]
+                let _ = mmap.advise(Advice::HugePage);
+                return Aligned {
+                    storage: Storage::Mapped(mmap),
+                    len,
+                    values: PhantomData,
+                };
+            }
+        }
+        Aligned {
+            storage: Storage::Heap(vec![CacheLine([0; CACHE_LINE_WORDS]); lines]),
+            len,
+            values: PhantomData,
+        }
+    }
+
+    /// Copies `values` into an aligned buffer.
+    pub fn from_slice(values: &[T], pages: Pages) -> Self {
+        let mut aligned = Self::zeroed(values.len(), pages);
+        aligned.copy_from_slice(values);
+        aligned
+    }
+
+    /// The bits of the cache lines which hold the values, including the unused end of the last one.
+    pub fn line_bits(&self) -> usize {
+        Self::lines(self.len) * mem::size_of::<CacheLine>() * 8
+    }
+
+    // The number of cache lines which hold `len` values.
+    fn lines(len: usize) -> usize {
+        (len * mem::size_of::<T>()).div_ceil(mem::size_of::<CacheLine>())
+    }
+
+    /// Whether the words are mapped for huge pages rather than allocated on the heap.
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.storage, Storage::Mapped(_))
+    }
+
+    fn as_ptr(&self) -> *const T {
+        match &self.storage {
+            Storage::Heap(lines) => lines.as_ptr() as *const T,
+            Storage::Mapped(mmap) => mmap.as_ptr() as *const T,
+        }
+    }
+}
+
+impl<T: Zeroable> Deref for Aligned<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // Safety: Both storages are aligned to 64 bytes and hold at least `len` zeroed or written
+        // values, and the pointer of an empty vector is aligned and not null.
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Zeroable> DerefMut for Aligned<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        let ptr = match &mut self.storage {
+            Storage::Heap(lines) => lines.as_mut_ptr() as *mut T,
+            Storage::Mapped(mmap) => mmap.as_mut_ptr() as *mut T,
+        };
+        // Safety: See `deref`, and the storage is borrowed mutably.
+        unsafe { slice::from_raw_parts_mut(ptr, self.len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligned() {
+        for pages in [Pages::Normal, Pages::Huge] {
+            for len in [0, 1, 8, 1000] {
+                let words: Vec<u64> = (0..len as u64).collect();
+                let aligned = AlignedWords::from_slice(&words, pages);
+                assert_eq!(*aligned, words[..]);
+                assert_eq!(aligned.as_ptr() as usize % 64, 0);
+            }
+        }
+        assert!(!AlignedWords::zeroed(10, Pages::Normal).is_mapped());
+    }
+
+    #[test]
+    fn other_values() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        #[repr(align(16))]
+        struct Pair(u64, u64);
+        // Safety: Zero is valid for both integers and the alignment is 16 bytes.
+        unsafe impl Zeroable for Pair {}
+
+        let pairs: Vec<Pair> = (0..5).map(|i| Pair(i, i * i)).collect();
+        let aligned = Aligned::from_slice(&pairs, Pages::Huge);
+        assert_eq!(*aligned, pairs[..]);
+        assert_eq!(aligned.as_ptr() as usize % 64, 0);
+        assert_eq!(aligned.line_bits(), 2 * 512);
+        assert_eq!(Aligned::<Pair>::zeroed(0, Pages::Normal).line_bits(), 0);
+    }
+}
@@ -0,0 +1,444 @@
+//! # BalancedParentheses
+//!
+//! A sequence of parentheses stored in a [`RunawayVector`], where a one is an opening and a zero
+//! a closing parenthesis. A range min-max tree as presented by
+//! [Navarro and Sadakane](https://doi.org/10.1145/2601073) over the excess values answers the
+//! forward and backward searches which all parenthesis matching operations are built from.
+//!
+//! The excess `excess(k)` is the number of opening minus the number of closing parentheses in
+//! front of position `k`, so `k` goes from `0` to the length of the sequence. Each leaf of the tree
+//! covers [`BLOCK_BIT_SIZE`] parentheses and stores the minimum and maximum excess of its range.
+//! Inside a leaf the excess is scanned a byte at a time with lookup tables.
+//!
+use std::{cmp, mem};
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::runaway_vector::RunawayVector;
+use crate::space_report::{self, SpaceReport};
+
+pub const BLOCK_BIT_SIZE: usize = 1024;
+
+// The excess after all 8 bits of a byte.
+const BYTE_DELTA: [i8; 256] = byte_table(8, 9, false);
+// The minimum and maximum excess in front of each bit of a byte, used when scanning backwards.
+const BYTE_MIN_BEFORE: [i8; 256] = byte_table(0, 8, false);
+const BYTE_MAX_BEFORE: [i8; 256] = byte_table(0, 8, true);
+// The minimum and maximum excess behind each bit of a byte, used when scanning forwards.
+const BYTE_MIN_AFTER: [i8; 256] = byte_table(1, 9, false);
+const BYTE_MAX_AFTER: [i8; 256] = byte_table(1, 9, true);
+
+pub struct BalancedParentheses {
+    bits: RunawayVector<'static>,
+    // The minimum and maximum excess of each node. The nodes are stored as a heap with the root at
+    // index 1, padded to a complete binary tree.
+    mins: Vec<i64>,
+    maxs: Vec<i64>,
+    leaf_count: usize,
+}
+
+impl BalancedParentheses {
+    pub fn new(bit_vec: BitVec<u64, Lsb0>) -> Self {
+        let len = bit_vec.len();
+        let leaf_count = cmp::max(len.div_ceil(BLOCK_BIT_SIZE), 1).next_power_of_two();
+        let mut mins = vec![i64::MAX; 2 * leaf_count];
+        let mut maxs = vec![i64::MIN; 2 * leaf_count];
+
+        let mut excess = 0;
+        for leaf in 0..len.div_ceil(BLOCK_BIT_SIZE) {
+            // Each leaf covers the excess in front of its first bit up to behind its last bit.
+            let mut min = excess;
+            let mut max = excess;
+            for bit in
+                bit_vec[leaf * BLOCK_BIT_SIZE..cmp::min((leaf + 1) * BLOCK_BIT_SIZE, len)].iter()
+            {
+                excess += if *bit { 1 } else { -1 };
+                min = cmp::min(min, excess);
+                max = cmp::max(max, excess);
+            }
+            mins[leaf_count + leaf] = min;
+            maxs[leaf_count + leaf] = max;
+        }
+        if len == 0 {
+            mins[leaf_count] = 0;
+            maxs[leaf_count] = 0;
+        }
+        for node in (1..leaf_count).rev() {
+            mins[node] = cmp::min(mins[2 * node], mins[2 * node + 1]);
+            maxs[node] = cmp::max(maxs[2 * node], maxs[2 * node + 1]);
+        }
+
+        BalancedParentheses {
+            bits: RunawayVector::from_owned(bit_vec),
+            mins,
+            maxs,
+            leaf_count,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// The parentheses with their rank and select index.
+    pub fn bits(&self) -> &RunawayVector<'static> {
+        &self.bits
+    }
+
+    /// Whether the parenthesis at `idx` is an opening one.
+    pub fn is_open(&self, idx: usize) -> bool {
+        self.bits.access(idx)
+    }
+
+    /// Whether every parenthesis is matched and no prefix closes more than it opens.
+    pub fn is_balanced(&self) -> bool {
+        self.excess(self.len()) == 0 && self.mins[1] >= 0
+    }
+
+    /// The number of opening minus the number of closing parentheses in front of `idx`.
+    pub fn excess(&self, idx: usize) -> i64 {
+        2 * self.bits.rank1(idx) as i64 - idx as i64
+    }
+
+    /// Finds the closing parenthesis which matches the opening one at `idx`.
+    pub fn find_close(&self, idx: usize) -> Option<usize> {
+        debug_assert!(self.is_open(idx));
+        self.fwd_search(idx, 0).map(|k| k - 1)
+    }
+
+    /// Finds the opening parenthesis which matches the closing one at `idx`.
+    pub fn find_open(&self, idx: usize) -> Option<usize> {
+        debug_assert!(!self.is_open(idx));
+        self.bwd_search(idx + 1, 0)
+    }
+
+    /// Finds the opening parenthesis of the closest pair which encloses the one opened at `idx`.
+    pub fn enclose(&self, idx: usize) -> Option<usize> {
+        debug_assert!(self.is_open(idx));
+        self.bwd_search(idx, -1)
+    }
+
+    /// Finds the smallest `k > idx` with `excess(k) == excess(idx) + diff`.
+    pub fn fwd_search(&self, idx: usize, diff: i64) -> Option<usize> {
+        assert!(idx <= self.len());
+        let target = self.excess(idx) + diff;
+        if idx == self.len() {
+            return None;
+        }
+        // Search the rest of the leaf of `idx`.
+        let leaf = idx / BLOCK_BIT_SIZE;
+        let leaf_end = self.leaf_end(leaf);
+        if let Some(k) = self.scan_forward(idx, leaf_end, self.excess(idx), target) {
+            return Some(k);
+        }
+        // Go up until a right sibling contains the target and then down to its leftmost leaf
+        // containing it. As the excess changes by one per bit, a node contains the target if it is
+        // between its minimum and maximum.
+        let mut node = self.leaf_count + leaf;
+        while node > 1 {
+            if node.is_multiple_of(2) && self.contains(node + 1, target) {
+                node += 1;
+                while node < self.leaf_count {
+                    node = if self.contains(2 * node, target) {
+                        2 * node
+                    } else {
+                        2 * node + 1
+                    };
+                }
+                let leaf = node - self.leaf_count;
+                let start = leaf * BLOCK_BIT_SIZE;
+                return self.scan_forward(start, self.leaf_end(leaf), self.excess(start), target);
+            }
+            node /= 2;
+        }
+        None
+    }
+
+    /// Finds the largest `k < idx` with `excess(k) == excess(idx) + diff`.
+    pub fn bwd_search(&self, idx: usize, diff: i64) -> Option<usize> {
+        assert!(idx <= self.len());
+        let target = self.excess(idx) + diff;
+        if idx == 0 {
+            return None;
+        }
+        // Search the rest of the leaf of `idx - 1`, which is the first candidate.
+        let leaf = (idx - 1) / BLOCK_BIT_SIZE;
+        let leaf_start = leaf * BLOCK_BIT_SIZE;
+        if let Some(k) = self.scan_backward(leaf_start, idx, self.excess(idx), target) {
+            return Some(k);
+        }
+        // Same as the forward search, but with left siblings and rightmost leaves.
+        let mut node = self.leaf_count + leaf;
+        while node > 1 {
+            if node % 2 == 1 && self.contains(node - 1, target) {
+                node -= 1;
+                while node < self.leaf_count {
+                    node = if self.contains(2 * node + 1, target) {
+                        2 * node + 1
+                    } else {
+                        2 * node
+                    };
+                }
+                let leaf = node - self.leaf_count;
+                let end = self.leaf_end(leaf);
+                return self.scan_backward(leaf * BLOCK_BIT_SIZE, end, self.excess(end), target);
+            }
+            node /= 2;
+        }
+        None
+    }
+
+    /// Finds the leftmost `k` in `start..=end` with the minimal excess.
+    pub fn range_min(&self, start: usize, end: usize) -> usize {
+        assert!(start <= end && end <= self.len());
+        let min = self.range_min_excess(start, end);
+        if self.excess(start) == min {
+            start
+        } else {
+            // Every excess in between is larger, so the first position reaching the minimum is it.
+            self.fwd_search(start, min - self.excess(start)).unwrap()
+        }
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.bits.len()
+            + self.bits.space_usage()
+            + (self.mins.len() + self.maxs.len()) * 64
+            + mem::size_of::<Self>()
+    }
+
+    /// The excess tree is reported as other support.
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            other: space_report::bits(&self.mins) + space_report::bits(&self.maxs),
+            slack: space_report::slack(&self.mins) + space_report::slack(&self.maxs),
+            ..SpaceReport::of::<Self>()
+        } + self.bits.space_report().inline()
+    }
+
+    fn range_min_excess(&self, start: usize, end: usize) -> i64 {
+        let start_leaf = start / BLOCK_BIT_SIZE;
+        let end_leaf = end / BLOCK_BIT_SIZE;
+        if start_leaf == end_leaf || end_leaf - start_leaf == 1 {
+            return self.scan_min(start, end);
+        }
+        // Scan the partial leaves at the borders and take the full leaves in between from the tree.
+        let mut min = cmp::min(
+            self.scan_min(start, self.leaf_end(start_leaf)),
+            self.scan_min(end_leaf * BLOCK_BIT_SIZE, end),
+        );
+        let mut left = self.leaf_count + start_leaf + 1;
+        let mut right = self.leaf_count + end_leaf;
+        while left < right {
+            if left % 2 == 1 {
+                min = cmp::min(min, self.mins[left]);
+                left += 1;
+            }
+            if right % 2 == 1 {
+                right -= 1;
+                min = cmp::min(min, self.mins[right]);
+            }
+            left /= 2;
+            right /= 2;
+        }
+        min
+    }
+
+    fn contains(&self, node: usize, target: i64) -> bool {
+        self.mins[node] <= target && target <= self.maxs[node]
+    }
+
+    fn leaf_end(&self, leaf: usize) -> usize {
+        cmp::min((leaf + 1) * BLOCK_BIT_SIZE, self.len())
+    }
+
+    fn byte(&self, idx: usize) -> usize {
+        let words = self.bits.words();
+        ((words[idx / 64] >> (idx % 64)) & 0xff) as usize
+    }
+
+    // Finds the smallest `k` in `start + 1..=end` with an excess of `target`.
+    fn scan_forward(&self, start: usize, end: usize, excess: i64, target: i64) -> Option<usize> {
+        let mut excess = excess;
+        let mut idx = start;
+        while idx < end {
+            if idx.is_multiple_of(8) && idx + 8 <= end {
+                let byte = self.byte(idx);
+                if excess + (BYTE_MIN_AFTER[byte] as i64) > target
+                    || excess + (BYTE_MAX_AFTER[byte] as i64) < target
+                {
+                    excess += BYTE_DELTA[byte] as i64;
+                    idx += 8;
+                    continue;
+                }
+            }
+            excess += if self.is_open(idx) { 1 } else { -1 };
+            idx += 1;
+            if excess == target {
+                return Some(idx);
+            }
+        }
+        None
+    }
+
+    // Finds the largest `k` in `start..end` with an excess of `target`.
+    fn scan_backward(&self, start: usize, end: usize, excess: i64, target: i64) -> Option<usize> {
+        let mut excess = excess;
+        let mut idx = end;
+        while idx > start {
+            if idx.is_multiple_of(8) && idx >= start + 8 {
+                let byte = self.byte(idx - 8);
+                let before = excess - BYTE_DELTA[byte] as i64;
+                if before + (BYTE_MIN_BEFORE[byte] as i64) > target
+                    || before + (BYTE_MAX_BEFORE[byte] as i64) < target
+                {
+                    excess = before;
+                    idx -= 8;
+                    continue;
+                }
+            }
+            idx -= 1;
+            excess -= if self.is_open(idx) { 1 } else { -1 };
+            if excess == target {
+                return Some(idx);
+            }
+        }
+        None
+    }
+
+    // The minimal excess of `start..=end`.
+    fn scan_min(&self, start: usize, end: usize) -> i64 {
+        let mut excess = self.excess(start);
+        let mut min = excess;
+        let mut idx = start;
+        while idx < end {
+            if idx.is_multiple_of(8) && idx + 8 <= end {
+                let byte = self.byte(idx);
+                min = cmp::min(min, excess + BYTE_MIN_AFTER[byte] as i64);
+                excess += BYTE_DELTA[byte] as i64;
+                idx += 8;
+            } else {
+                excess += if self.is_open(idx) { 1 } else { -1 };
+                min = cmp::min(min, excess);
+                idx += 1;
+            }
+        }
+        min
+    }
+}
+
+// Builds a table with the minimum or maximum excess in front of the bits `start..end` of each
+// byte, where the excess in front of bit 8 is the excess behind the whole byte.
+const fn byte_table(start: usize, end: usize, maximum: bool) -> [i8; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut excess = 0;
+        let mut value = if maximum { i8::MIN } else { i8::MAX };
+        let mut bit = 0;
+        while bit < end {
+            if bit >= start && ((maximum && excess > value) || (!maximum && excess < value)) {
+                value = excess;
+            }
+            if bit < 8 {
+                excess += if (byte >> bit) & 1 == 1 { 1 } else { -1 };
+            }
+            bit += 1;
+        }
+        table[byte] = value;
+        byte += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    /// A random balanced sequence of `pairs` pairs of parentheses.
+    pub(crate) fn random_parentheses(pairs: usize, seed: u64) -> BitVec<u64, Lsb0> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut bit_vec = BitVec::with_capacity(2 * pairs);
+        let mut open = 0;
+        let mut remaining = pairs;
+        while remaining > 0 || open > 0 {
+            if remaining > 0 && (open == 0 || rng.gen_bool(0.5)) {
+                bit_vec.push(true);
+                open += 1;
+                remaining -= 1;
+            } else {
+                bit_vec.push(false);
+                open -= 1;
+            }
+        }
+        bit_vec
+    }
+
+    fn naive_excess(bit_vec: &BitVec<u64, Lsb0>) -> Vec<i64> {
+        let mut excess = vec![0];
+        for bit in bit_vec.iter() {
+            excess.push(excess.last().unwrap() + if *bit { 1 } else { -1 });
+        }
+        excess
+    }
+
+    #[test]
+    fn byte_tables() {
+        assert_eq!(BYTE_DELTA[0], -8);
+        assert_eq!(BYTE_DELTA[0xff], 8);
+        assert_eq!(BYTE_MIN_AFTER[0b0000_0001], -6);
+        assert_eq!(BYTE_MAX_AFTER[0b0000_0001], 1);
+        assert_eq!(BYTE_MIN_BEFORE[0b1111_1110], -1);
+        assert_eq!(BYTE_MAX_BEFORE[0b0000_0001], 1);
+    }
+
+    #[test]
+    fn searches() {
+        let bit_vec = random_parentheses(3 * BLOCK_BIT_SIZE, 7);
+        let excess = naive_excess(&bit_vec);
+        let parentheses = BalancedParentheses::new(bit_vec);
+        assert!(parentheses.is_balanced());
+        for idx in (0..excess.len()).step_by(7) {
+            for diff in [-3, -1, 0, 1, 2] {
+                let target = excess[idx] + diff;
+                let fwd = (idx + 1..excess.len()).find(|k| excess[*k] == target);
+                let bwd = (0..idx).rev().find(|k| excess[*k] == target);
+                assert_eq!(parentheses.fwd_search(idx, diff), fwd);
+                assert_eq!(parentheses.bwd_search(idx, diff), bwd);
+            }
+        }
+    }
+
+    #[test]
+    fn range_min() {
+        let bit_vec = random_parentheses(5 * BLOCK_BIT_SIZE / 2, 3);
+        let excess = naive_excess(&bit_vec);
+        let parentheses = BalancedParentheses::new(bit_vec);
+        for (start, end) in [
+            (0, 0),
+            (3, 100),
+            (17, 2500),
+            (1000, 4900),
+            (0, excess.len() - 1),
+        ] {
+            let min = excess[start..=end].iter().min().unwrap();
+            let expected = start + excess[start..=end].iter().position(|e| e == min).unwrap();
+            assert_eq!(parentheses.range_min(start, end), expected);
+        }
+    }
+
+    #[test]
+    fn unbalanced() {
+        let mut bit_vec = BitVec::new();
+        bit_vec.extend([false, true]);
+        assert!(!BalancedParentheses::new(bit_vec).is_balanced());
+        assert!(BalancedParentheses::new(BitVec::new()).is_balanced());
+    }
+}
@@ -0,0 +1,112 @@
+use runaway_datastructures::fm_index::FmIndex;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::Sub;
+use std::path::Path;
+use std::time::Instant;
+use std::{env, io};
+
+const DEFAULT_SAMPLE_RATE: usize = 32;
+
+/// Builds an FM-index from a text file or answers queries on an index file.
+///
+/// `fm_index build [text] [index] [sample rate]` writes the index of the text.
+///
+/// `fm_index query [index] [queries] [output]` reads one query per line, either `count [pattern]`,
+/// `locate [pattern]` or `extract [from] [to]`, and writes one result per line.
+fn main() -> Result<(), io::Error> {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("build") if args.len() >= 4 => build(&args),
+        Some("query") if args.len() >= 5 => query(&args),
+        _ => {
+            eprintln!("usage: fm_index build [text] [index] [sample rate]");
+            eprintln!("       fm_index query [index] [queries] [output]");
+            Err(io::Error::from(io::ErrorKind::InvalidInput))
+        }
+    }
+}
+
+fn build(args: &[String]) -> Result<(), io::Error> {
+    let sample_rate = match args.get(4) {
+        Some(rate) => rate
+            .parse::<usize>()
+            .ok()
+            .filter(|rate| *rate > 0)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?,
+        None => DEFAULT_SAMPLE_RATE,
+    };
+    let mut text = Vec::new();
+    File::open(Path::new(&args[2]))?.read_to_end(&mut text)?;
+
+    let start = Instant::now();
+    let index = FmIndex::new(&text, sample_rate);
+    let build_elapsed = start.elapsed();
+
+    let mut writer = BufWriter::new(File::create(Path::new(&args[3]))?);
+    index.write_to(&mut writer)?;
+    writer.flush()?;
+
+    let report = index.space_report();
+    println!(
+        "RESULT name=FmIndex build={:?} len={} space={} overhead={}",
+        build_elapsed.as_millis(),
+        text.len(),
+        report.total(),
+        report.total() as f64 / (text.len() * 8) as f64,
+    );
+    Ok(())
+}
+
+fn query(args: &[String]) -> Result<(), io::Error> {
+    let start = Instant::now();
+    let index = FmIndex::read_from(&mut BufReader::new(File::open(Path::new(&args[2]))?))?;
+    let read_elapsed = start.elapsed();
+
+    let reader = BufReader::new(File::open(Path::new(&args[3]))?);
+    let mut results = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let result = if let Some(pattern) = line.strip_prefix("count ") {
+            format!("{}\n", index.count(pattern.as_bytes()))
+        } else if let Some(pattern) = line.strip_prefix("locate ") {
+            let positions: Vec<String> = index
+                .locate(pattern.as_bytes())
+                .iter()
+                .map(|pos| pos.to_string())
+                .collect();
+            format!("{}\n", positions.join(" "))
+        } else if let Some(range) = line.strip_prefix("extract ") {
+            let (from, to) = range
+                .split_once(' ')
+                .and_then(|(from, to)| Some((from.parse().ok()?, to.trim().parse().ok()?)))
+                .filter(|(from, to): &(usize, usize)| from <= to && *to <= index.len())
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+            let mut extracted = index.extract(from..to);
+            extracted.push(b'\n');
+            String::from_utf8_lossy(&extracted).into_owned()
+        } else {
+            return Err(io::Error::from(io::ErrorKind::InvalidData));
+        };
+        results.push(result);
+    }
+    let process_elapsed = start.elapsed();
+
+    println!(
+        "RESULT name=FmIndex time={:?} read={:?} queries={}",
+        process_elapsed.sub(read_elapsed).as_millis(),
+        read_elapsed.as_millis(),
+        results.len(),
+    );
+
+    let mut file_output = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(Path::new(&args[4]))?;
+    results.iter().for_each(|line| {
+        file_output.write_all(line.as_bytes()).unwrap();
+    });
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use bitvec::vec::BitVec;
+use runaway_datastructures::runaway_vector::{RunawayVector, L1_BIT_SIZE};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::{env, io};
+
+// Dumps the index of the bits in a query file for the L1 blocks which overlap a range of
+// positions: inspect <input> <start> <end>
+fn main() -> Result<(), io::Error> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 4 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "usage: inspect <input> <start> <end>",
+        ));
+    }
+    let parse = |arg: &str| {
+        arg.parse::<usize>()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    };
+    let (start, end) = (parse(&args[2])?, parse(&args[3])?);
+
+    // The bits are on the second line, like for the query reader.
+    let mut lines = BufReader::new(File::open(Path::new(&args[1]))?).lines();
+    lines.next().transpose()?;
+    let line = lines.next().transpose()?.unwrap_or_default();
+    let bit_vec: BitVec<u64> = line
+        .trim_end()
+        .chars()
+        .map(|char| match char {
+            '1' => Ok(true),
+            '0' => Ok(false),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "not a bit")),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let vector = RunawayVector::new(&bit_vec);
+    println!("{:?}", vector.index());
+    println!("l0 entries: {:?}", vector.index().l0_entries());
+    println!("checksum: {:#018x}", vector.checksum());
+    println!("verify: {:?}", vector.verify());
+
+    let end = end.min(vector.len());
+    if start < end {
+        for i in start / L1_BIT_SIZE..end.div_ceil(L1_BIT_SIZE) {
+            println!("{:#?}", vector.l1_block(i));
+        }
+    }
+    Ok(())
+}
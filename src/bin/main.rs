@@ -29,11 +29,12 @@ pub fn main() {
     //    dbg!(vector.select0(idx));
     //}
     dbg!(vector.select1(1 << 32));
-    let space = rand_bv.len() + vector.space_usage();
+    let report = vector.space_report();
+    print!("{report}");
     println!(
         "RESULT name=Nasarek space={} support_space={} overhead={}",
-        space,
-        vector.space_usage(),
-        vector.space_usage() as f64 / space as f64,
+        report.total(),
+        report.support(),
+        report.support() as f64 / report.total() as f64,
     );
 }
@@ -1,5 +1,10 @@
 use bitvec::vec::BitVec;
+use runaway_datastructures::aligned::Pages;
+use runaway_datastructures::interleaved_vector::InterleavedVector;
+use runaway_datastructures::naive_vector::NaiveVector;
 use runaway_datastructures::query::{Query, QueryResult};
+use runaway_datastructures::rank9::{Rank9, SimpleSelect};
+use runaway_datastructures::rank_select::RankSelect;
 use runaway_datastructures::runaway_vector::RunawayVector;
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
@@ -37,26 +42,45 @@ fn main() -> Result<(), io::Error> {
     }
     let read_elapsed = start.elapsed();
 
-    let vector = RunawayVector::new(&bit_vec);
+    // The vector to answer the queries with is chosen by the optional third argument.
+    let name = args.get(3).map_or("runaway", String::as_str);
+    let vector: Box<dyn RankSelect> = match name {
+        "runaway" => Box::new(RunawayVector::new(&bit_vec)),
+        "runaway_huge" => Box::new(RunawayVector::with_pages(&bit_vec, Pages::Huge)),
+        "naive" => Box::new(NaiveVector::new(&bit_vec, 512)),
+        "rank9" => Box::new(Rank9::new(&bit_vec)),
+        "simple_select" => Box::new(SimpleSelect::new(&bit_vec)),
+        "interleaved" => Box::new(InterleavedVector::new(&bit_vec, Pages::Huge)),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown vector {name}"),
+            ))
+        }
+    };
     let build_elapsed = start.elapsed();
 
     let results: Vec<QueryResult> = queries.iter().map(|query| vector.process(query)).collect();
     let build_and_process_elapsed = start.elapsed();
 
-    let space = bit_vec.len() + vector.space_usage();
+    // The report counts the bits of the vector as its data.
+    let report = vector.space_report();
     println!(
-        "RESULT name=Nasarek time={:?} build={:?} read={:?} space={} overhead={}",
+        "RESULT name=Nasarek time={:?} build={:?} read={:?} space={} overhead={} vector={}",
         build_and_process_elapsed.sub(read_elapsed).as_millis(),
         build_elapsed.sub(read_elapsed).as_millis(),
         read_elapsed.as_millis(),
-        space,
-        space as f64 / bit_vec.len() as f64,
+        report.total(),
+        report.total() as f64 / bit_vec.len() as f64,
+        name,
     );
+    println!("SPACE {}", report.to_json());
 
     let path_output = Path::new(&args[2]);
     let mut file_output = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(path_output)?;
     results
         .iter()
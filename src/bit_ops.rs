@@ -0,0 +1,221 @@
+//! # Bit operations
+//!
+//! Set algebra on two bit vectors of the same length, a word at a time. [`combine`] writes the
+//! result and builds its rank and select index in the same pass over the words, while
+//! [`combine_bits`] only writes the bits, for results which are indexed later or not at all.
+//!
+//! When only counts are needed, a [`CombinedView`] answers rank queries on the result without
+//! materializing it, by combining the words of both vectors up to the queried position.
+//!
+use std::borrow::Cow;
+
+use bitvec::field::BitField;
+use bitvec::order::Lsb0;
+use bitvec::prelude::{BitSlice, BitVec};
+
+use crate::runaway_vector::{IndexBuilder, RunawayVector};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    /// The bits of the left vector which are not set in the right one.
+    AndNot,
+}
+
+impl BitOp {
+    pub fn apply(&self, left: u64, right: u64) -> u64 {
+        match self {
+            BitOp::And => left & right,
+            BitOp::Or => left | right,
+            BitOp::Xor => left ^ right,
+            BitOp::AndNot => left & !right,
+        }
+    }
+}
+
+/// Combines the bits of `left` and `right` with `op` and builds the index of the result while the
+/// words are written.
+pub fn combine(left: &RunawayVector, right: &RunawayVector, op: BitOp) -> RunawayVector<'static> {
+    let (left, right) = (left.bit_vec(), right.bit_vec());
+    assert_eq!(left.len(), right.len(), "vectors differ in length");
+    let mut builder = IndexBuilder::new(left.len());
+    let words: Vec<u64> = words(left, right, op)
+        .inspect(|word| builder.push(*word))
+        .collect();
+    builder.finish(Cow::Owned(into_bit_vec(words, left.len())))
+}
+
+/// Combines the bits of `left` and `right` with `op` without building an index. The bits may
+/// start anywhere in their first word, like slices of other vectors.
+pub fn combine_bits(
+    left: &BitSlice<u64, Lsb0>,
+    right: &BitSlice<u64, Lsb0>,
+    op: BitOp,
+) -> BitVec<u64, Lsb0> {
+    assert_eq!(left.len(), right.len(), "vectors differ in length");
+    into_bit_vec(words(left, right, op).collect(), left.len())
+}
+
+// The combined words, which are loaded from the bits rather than read from their raw words, so
+// they start at the first bit of `left` and `right` even if that is not the lowest bit of a word.
+fn words<'b>(
+    left: &'b BitSlice<u64, Lsb0>,
+    right: &'b BitSlice<u64, Lsb0>,
+    op: BitOp,
+) -> impl Iterator<Item = u64> + 'b {
+    left.chunks(64)
+        .zip(right.chunks(64))
+        .map(move |(left, right)| op.apply(left.load_le(), right.load_le()))
+}
+
+fn into_bit_vec(words: Vec<u64>, len: usize) -> BitVec<u64, Lsb0> {
+    let mut bit_vec = BitVec::from_vec(words);
+    bit_vec.truncate(len);
+    bit_vec
+}
+
+/// The result of a [`BitOp`] on two vectors which is computed on demand.
+pub struct CombinedView<'b> {
+    left: &'b BitSlice<u64, Lsb0>,
+    right: &'b BitSlice<u64, Lsb0>,
+    op: BitOp,
+}
+
+impl<'b> CombinedView<'b> {
+    pub fn new(left: &'b RunawayVector, right: &'b RunawayVector, op: BitOp) -> Self {
+        assert_eq!(left.len(), right.len(), "vectors differ in length");
+        CombinedView {
+            left: left.bit_vec(),
+            right: right.bit_vec(),
+            op,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.left.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+
+    pub fn access(&self, idx: usize) -> bool {
+        assert!(idx < self.len());
+        self.op.apply(self.left[idx] as u64, self.right[idx] as u64) & 1 == 1
+    }
+
+    /// Counts the ones of the result in front of `idx` in `O(idx / 64)` without allocating.
+    pub fn rank1(&self, idx: usize) -> usize {
+        assert!(idx <= self.len());
+        let full = idx / 64;
+        let mut words = words(self.left, self.right, self.op);
+        let mut count: usize = words
+            .by_ref()
+            .take(full)
+            .map(|word| word.count_ones() as usize)
+            .sum();
+        if !idx.is_multiple_of(64) {
+            count += (words.next().unwrap() & ((1 << (idx % 64)) - 1)).count_ones() as usize;
+        }
+        count
+    }
+
+    pub fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank1(idx)
+    }
+
+    /// Counts all ones of the result.
+    pub fn count_ones(&self) -> usize {
+        self.rank1(self.len())
+    }
+
+    /// Writes the result and builds its index.
+    pub fn materialize(&self) -> RunawayVector<'static> {
+        let mut builder = IndexBuilder::new(self.len());
+        let words: Vec<u64> = words(self.left, self.right, self.op)
+            .inspect(|word| builder.push(*word))
+            .collect();
+        builder.finish(Cow::Owned(into_bit_vec(words, self.len())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    const OPS: [BitOp; 4] = [BitOp::And, BitOp::Or, BitOp::Xor, BitOp::AndNot];
+
+    fn naive(left: bool, right: bool, op: BitOp) -> bool {
+        match op {
+            BitOp::And => left && right,
+            BitOp::Or => left || right,
+            BitOp::Xor => left != right,
+            BitOp::AndNot => left && !right,
+        }
+    }
+
+    #[test]
+    fn combine() {
+        let mut rng = SmallRng::seed_from_u64(23);
+        for len in [0, 1, 64, 100, 2048, 5000] {
+            let left: BitVec<u64, Lsb0> = (0..len).map(|_| rng.gen_bool(0.5)).collect();
+            let right: BitVec<u64, Lsb0> = (0..len).map(|_| rng.gen_bool(0.3)).collect();
+            let (l, r) = (RunawayVector::new(&left), RunawayVector::new(&right));
+            for op in OPS {
+                let expected: BitVec<u64, Lsb0> =
+                    (0..len).map(|i| naive(left[i], right[i], op)).collect();
+                let combined = super::combine(&l, &r, op);
+                assert_eq!(*combined.bit_vec(), expected);
+                assert_eq!(combine_bits(&left, &right, op), expected);
+                let indexed = RunawayVector::new(&expected);
+                for i in 0..=len {
+                    assert_eq!(combined.rank1(i), indexed.rank1(i));
+                }
+                let ones = indexed.rank1(len);
+                for rank in 1..=ones {
+                    assert_eq!(combined.select1(rank), indexed.select1(rank));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unaligned() {
+        let mut rng = SmallRng::seed_from_u64(31);
+        let left: BitVec<u64, Lsb0> = (0..700).map(|_| rng.gen_bool(0.5)).collect();
+        let right: BitVec<u64, Lsb0> = (0..703).map(|_| rng.gen_bool(0.5)).collect();
+        for op in OPS {
+            let expected: BitVec<u64, Lsb0> = (0..690)
+                .map(|i| naive(left[i + 10], right[i + 13], op))
+                .collect();
+            assert_eq!(combine_bits(&left[10..], &right[13..], op), expected);
+        }
+    }
+
+    #[test]
+    fn view() {
+        let mut rng = SmallRng::seed_from_u64(29);
+        let left: BitVec<u64, Lsb0> = (0..3000).map(|_| rng.gen_bool(0.5)).collect();
+        let mut right: BitVec<u64, Lsb0> = (0..3000).map(|_| rng.gen_bool(0.5)).collect();
+        // The unused bits behind the end must not be counted.
+        right.extend([true; 20]);
+        right.truncate(3000);
+        let (l, r) = (RunawayVector::new(&left), RunawayVector::new(&right));
+        for op in OPS {
+            let view = CombinedView::new(&l, &r, op);
+            let combined = super::combine(&l, &r, op);
+            for i in 0..=3000 {
+                assert_eq!(view.rank1(i), combined.rank1(i));
+            }
+            for i in 0..3000 {
+                assert_eq!(view.access(i), naive(left[i], right[i], op));
+            }
+            assert_eq!(view.count_ones(), combined.rank1(3000));
+            assert_eq!(view.materialize().bit_vec(), combined.bit_vec());
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! # BpTree
+//!
+//! An ordinal tree stored as its balanced parentheses sequence. The tree is traversed in depth
+//! first order, writing an opening parenthesis when entering and a closing one when leaving a node.
+//! A tree with `n` nodes therefore needs `2n` bits plus the [`BalancedParentheses`] index.
+//!
+//! Nodes are identified by the position of their opening parenthesis, so the root is always `0`.
+//!
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::balanced_parentheses::BalancedParentheses;
+use crate::space_report::SpaceReport;
+
+pub struct BpTree {
+    parentheses: BalancedParentheses,
+}
+
+impl BpTree {
+    /// Builds the tree from its parentheses, where a one is an opening parenthesis.
+    pub fn new(bit_vec: BitVec<u64, Lsb0>) -> Self {
+        let parentheses = BalancedParentheses::new(bit_vec);
+        assert!(parentheses.is_balanced(), "parentheses are not balanced");
+        // A forest with several roots is not a tree.
+        assert!(parentheses.is_empty() || parentheses.find_close(0) == Some(parentheses.len() - 1));
+        BpTree { parentheses }
+    }
+
+    /// Builds the tree from the children of each node, where node `0` is the root.
+    pub fn from_children(children: &[Vec<usize>]) -> Self {
+        let mut bit_vec = BitVec::with_capacity(2 * children.len());
+        if !children.is_empty() {
+            // Each entry holds a node and the number of its children which have been visited.
+            let mut stack = vec![(0, 0)];
+            bit_vec.push(true);
+            while let Some((node, visited)) = stack.last_mut() {
+                if let Some(child) = children[*node].get(*visited) {
+                    *visited += 1;
+                    stack.push((*child, 0));
+                    bit_vec.push(true);
+                } else {
+                    stack.pop();
+                    bit_vec.push(false);
+                }
+            }
+        }
+        Self::new(bit_vec)
+    }
+
+    /// The number of nodes.
+    pub fn len(&self) -> usize {
+        self.parentheses.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parentheses.is_empty()
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    pub fn parentheses(&self) -> &BalancedParentheses {
+        &self.parentheses
+    }
+
+    pub fn find_close(&self, idx: usize) -> usize {
+        self.parentheses.find_close(idx).unwrap()
+    }
+
+    pub fn find_open(&self, idx: usize) -> usize {
+        self.parentheses.find_open(idx).unwrap()
+    }
+
+    pub fn enclose(&self, idx: usize) -> Option<usize> {
+        self.parentheses.enclose(idx)
+    }
+
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        self.enclose(node)
+    }
+
+    pub fn first_child(&self, node: usize) -> Option<usize> {
+        Some(node + 1).filter(|child| self.parentheses.is_open(*child))
+    }
+
+    pub fn next_sibling(&self, node: usize) -> Option<usize> {
+        let sibling = self.find_close(node) + 1;
+        if sibling < self.parentheses.len() && self.parentheses.is_open(sibling) {
+            Some(sibling)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_leaf(&self, node: usize) -> bool {
+        self.first_child(node).is_none()
+    }
+
+    /// The number of nodes in the subtree of `node`, including itself.
+    pub fn subtree_size(&self, node: usize) -> usize {
+        (self.find_close(node) - node) / 2 + 1
+    }
+
+    /// The number of edges between `node` and the root.
+    pub fn depth(&self, node: usize) -> usize {
+        self.parentheses.excess(node) as usize
+    }
+
+    /// The position of `node` in a depth first traversal, starting with 0.
+    pub fn preorder(&self, node: usize) -> usize {
+        self.parentheses.bits().rank1(node)
+    }
+
+    /// The node at position `preorder` in a depth first traversal.
+    pub fn node(&self, preorder: usize) -> usize {
+        assert!(preorder < self.len());
+        self.parentheses.bits().select1(preorder + 1).unwrap()
+    }
+
+    /// The lowest common ancestor of `a` and `b`.
+    pub fn lca(&self, a: usize, b: usize) -> usize {
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        if b <= self.find_close(a) {
+            return a;
+        }
+        // The leftmost minimal excess between both nodes is the opening parenthesis of the child of
+        // the ancestor which leads to `b`.
+        self.parent(self.parentheses.range_min(a, b)).unwrap()
+    }
+
+    /// The ancestor `levels` above `node`, which is `node` itself for 0.
+    pub fn level_ancestor(&self, node: usize, levels: usize) -> Option<usize> {
+        if levels == 0 {
+            return Some(node);
+        }
+        self.parentheses.bwd_search(node, -(levels as i64))
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.parentheses.space_usage()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        self.parentheses.space_report()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balanced_parentheses::tests::random_parentheses;
+
+    // Parent pointers and depths of all nodes, indexed by their opening parenthesis.
+    fn naive_tree(bit_vec: &BitVec<u64, Lsb0>) -> (Vec<Option<usize>>, Vec<usize>) {
+        let mut parents = vec![None; bit_vec.len()];
+        let mut depths = vec![0; bit_vec.len()];
+        let mut stack: Vec<usize> = Vec::new();
+        for (i, bit) in bit_vec.iter().enumerate() {
+            if *bit {
+                parents[i] = stack.last().copied();
+                depths[i] = stack.len();
+                stack.push(i);
+            } else {
+                stack.pop();
+            }
+        }
+        (parents, depths)
+    }
+
+    fn random_tree(pairs: usize, seed: u64) -> BitVec<u64, Lsb0> {
+        let mut bit_vec = BitVec::new();
+        bit_vec.push(true);
+        bit_vec.extend(random_parentheses(pairs - 1, seed).iter());
+        bit_vec.push(false);
+        bit_vec
+    }
+
+    #[test]
+    fn navigation() {
+        // The tree (()(()())) with the root at 0.
+        let mut bit_vec = BitVec::new();
+        bit_vec.extend([
+            true, true, false, true, true, false, true, false, false, false,
+        ]);
+        let tree = BpTree::new(bit_vec);
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.find_close(0), 9);
+        assert_eq!(tree.find_open(8), 3);
+        assert_eq!(tree.first_child(0), Some(1));
+        assert_eq!(tree.next_sibling(1), Some(3));
+        assert_eq!(tree.next_sibling(3), None);
+        assert_eq!(tree.first_child(3), Some(4));
+        assert!(tree.is_leaf(6));
+        assert_eq!(tree.parent(6), Some(3));
+        assert_eq!(tree.parent(0), None);
+        assert_eq!(tree.subtree_size(0), 5);
+        assert_eq!(tree.subtree_size(3), 3);
+        assert_eq!(tree.depth(6), 2);
+        assert_eq!(tree.lca(1, 6), 0);
+        assert_eq!(tree.lca(4, 6), 3);
+        assert_eq!(tree.lca(3, 6), 3);
+        assert_eq!(tree.level_ancestor(6, 2), Some(0));
+        assert_eq!(tree.level_ancestor(6, 3), None);
+        assert_eq!(tree.node(3), 4);
+        assert_eq!(tree.preorder(6), 4);
+    }
+
+    #[test]
+    fn from_children() {
+        let children = vec![vec![1, 2], vec![], vec![3, 4], vec![], vec![]];
+        let tree = BpTree::from_children(&children);
+        assert_eq!(tree.parentheses().len(), 10);
+        assert_eq!(tree.subtree_size(tree.node(2)), 3);
+    }
+
+    #[test]
+    fn random() {
+        let bit_vec = random_tree(3000, 11);
+        let (parents, depths) = naive_tree(&bit_vec);
+        let tree = BpTree::new(bit_vec.clone());
+        let nodes: Vec<usize> = bit_vec.iter_ones().collect();
+        for node in nodes.iter().step_by(3) {
+            assert_eq!(tree.parent(*node), parents[*node]);
+            assert_eq!(tree.depth(*node), depths[*node]);
+            let close = tree.find_close(*node);
+            assert_eq!(tree.find_open(close), *node);
+            let descendants = nodes.iter().filter(|n| **n > *node && **n < close).count();
+            assert_eq!(tree.subtree_size(*node), descendants + 1);
+            let mut ancestor = *node;
+            for levels in 0..=depths[*node] {
+                assert_eq!(tree.level_ancestor(*node, levels), Some(ancestor));
+                ancestor = parents[ancestor].unwrap_or(0);
+            }
+        }
+        for (a, b) in nodes.iter().step_by(97).zip(nodes.iter().rev().step_by(89)) {
+            let mut ancestors = vec![*a];
+            while let Some(parent) = parents[*ancestors.last().unwrap()] {
+                ancestors.push(parent);
+            }
+            let mut expected = *b;
+            while !ancestors.contains(&expected) {
+                expected = parents[expected].unwrap();
+            }
+            assert_eq!(tree.lca(*a, *b), expected);
+        }
+    }
+}
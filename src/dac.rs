@@ -0,0 +1,221 @@
+//! # Dac
+//!
+//! An array of integers stored with the directly addressable codes as presented by
+//! [Brisaboa, Ladra and Navarro](https://doi.org/10.1016/j.ipm.2012.08.003). Each value is split
+//! into chunks from its lowest bits upwards. The first level holds the first chunk of every value,
+//! the second level the second chunk of the values which do not fit into the first chunk and so on.
+//! A bit vector per level marks the values which continue on the next level, and its rank gives
+//! their position there, so `get` takes one rank per level.
+//!
+//! Small values only pay for the first chunk, while rare large values pay for all of theirs. The
+//! chunk widths can be given or chosen to minimize the space for the values.
+//!
+use std::mem;
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::int_vector::{width_of, IntVector};
+use crate::runaway_vector::RunawayVector;
+use crate::space_report::{self, SpaceReport};
+
+pub struct Dac {
+    len: usize,
+    levels: Vec<IntVector>,
+    // Marks the values which have another chunk on the next level, for all but the last level.
+    continues: Vec<RunawayVector<'static>>,
+}
+
+impl Dac {
+    /// Builds the array with the chunk widths which use the least space for `values`.
+    pub fn new(values: &[u64]) -> Self {
+        Self::with_widths(values, &optimal_widths(values))
+    }
+
+    /// Builds the array with the given chunk width for each level. The widths have to add up to at
+    /// least the width of the largest value.
+    pub fn with_widths(values: &[u64], widths: &[usize]) -> Self {
+        let max = values.iter().copied().max().unwrap_or(0);
+        assert!(!widths.is_empty(), "at least one level is needed");
+        assert!(
+            widths.iter().sum::<usize>() >= width_of(max),
+            "widths do not fit the largest value"
+        );
+
+        let mut levels = Vec::with_capacity(widths.len());
+        let mut continues = Vec::with_capacity(widths.len() - 1);
+        let mut remaining: Vec<u64> = values.to_vec();
+        for (level, width) in widths.iter().enumerate() {
+            let mut chunks = IntVector::with_capacity(*width, remaining.len());
+            let mut continued: BitVec<u64, Lsb0> = BitVec::with_capacity(remaining.len());
+            let mut next = Vec::new();
+            for value in remaining {
+                let rest = value.checked_shr(*width as u32).unwrap_or(0);
+                chunks.push(value & (u64::MAX >> (64 - width)));
+                continued.push(rest != 0);
+                if rest != 0 {
+                    next.push(rest);
+                }
+            }
+            levels.push(chunks);
+            if next.is_empty() {
+                break;
+            }
+            if level + 1 < widths.len() {
+                continues.push(RunawayVector::from_owned(continued));
+            }
+            remaining = next;
+        }
+
+        Dac {
+            len: values.len(),
+            levels,
+            continues,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The chunk width of each level which holds at least one chunk.
+    pub fn widths(&self) -> Vec<usize> {
+        self.levels.iter().map(|level| level.width()).collect()
+    }
+
+    pub fn get(&self, idx: usize) -> u64 {
+        assert!(idx < self.len);
+        let mut pos = idx;
+        let mut value = 0;
+        let mut shift = 0;
+        for (level, chunks) in self.levels.iter().enumerate() {
+            value |= chunks.get(pos) << shift;
+            match self.continues.get(level) {
+                Some(continues) if continues.access(pos) => pos = continues.rank1(pos),
+                _ => break,
+            }
+            shift += chunks.width();
+        }
+        value
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.len).map(|idx| self.get(idx))
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.levels
+            .iter()
+            .map(|level| level.space_usage())
+            .sum::<usize>()
+            + self
+                .continues
+                .iter()
+                .map(|continues| continues.len() + continues.space_usage())
+                .sum::<usize>()
+            + mem::size_of::<Self>()
+    }
+
+    /// The chunks on the levels are the data. The structs of the levels and of the vectors which
+    /// mark the continued values are stored in vectors, so they count as their overhead.
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            slack: space_report::slack(&self.levels) + space_report::slack(&self.continues),
+            ..SpaceReport::of::<Self>()
+        } + self.levels.iter().map(IntVector::space_report).sum()
+            + self.continues.iter().map(RunawayVector::space_report).sum()
+    }
+}
+
+/// Finds the chunk widths which minimize the bits for the chunks and the continuation bits of
+/// `values`, with a dynamic program over the bit at which each level starts.
+pub fn optimal_widths(values: &[u64]) -> Vec<usize> {
+    let max_width = width_of(values.iter().copied().max().unwrap_or(0));
+    // The number of values which still have bits from bit `b` onwards, so they reach the level
+    // which starts at `b`.
+    let mut reaching = vec![0; max_width + 1];
+    for value in values {
+        reaching[..width_of(*value)]
+            .iter_mut()
+            .for_each(|count| *count += 1);
+    }
+
+    // The least bits for all bits from `b` onwards and the width of the level which starts at `b`.
+    let mut cost = vec![0; max_width + 1];
+    let mut width = vec![0; max_width + 1];
+    for start in (0..max_width).rev() {
+        cost[start] = usize::MAX;
+        for end in start + 1..=max_width {
+            let mut bits = reaching[start] * (end - start);
+            if end < max_width {
+                bits += reaching[start] + cost[end];
+            }
+            if bits < cost[start] {
+                cost[start] = bits;
+                width[start] = end - start;
+            }
+        }
+    }
+
+    let mut widths = Vec::new();
+    let mut start = 0;
+    while start < max_width {
+        widths.push(width[start]);
+        start += width[start];
+    }
+    widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn outliers(len: usize, seed: u64) -> Vec<u64> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        (0..len)
+            .map(|_| match rng.gen_range(0..100) {
+                0 => rng.gen(),
+                1..=5 => rng.gen_range(0..1 << 20),
+                _ => rng.gen_range(0..16),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn get() {
+        let values = outliers(10_000, 1);
+        for widths in [&[4, 4, 8, 48][..], &[1; 64], &[64], &[3, 61]] {
+            let dac = Dac::with_widths(&values, widths);
+            assert_eq!(dac.iter().collect::<Vec<u64>>(), values);
+        }
+        let dac = Dac::new(&values);
+        assert_eq!(dac.iter().collect::<Vec<u64>>(), values);
+        assert!(dac.space_usage() < IntVector::from_slice(&values).space_usage() / 4);
+    }
+
+    #[test]
+    fn optimal_widths() {
+        assert_eq!(super::optimal_widths(&[]), vec![1]);
+        assert_eq!(super::optimal_widths(&[0, 1, 1, 0]), vec![1]);
+        assert_eq!(super::optimal_widths(&[255; 100]), vec![8]);
+        let widths = super::optimal_widths(&outliers(10_000, 2));
+        assert_eq!(widths.iter().sum::<usize>(), 64);
+        assert!(widths[0] <= 5);
+    }
+
+    #[test]
+    fn empty_and_small() {
+        let dac = Dac::new(&[]);
+        assert!(dac.is_empty());
+        let dac = Dac::with_widths(&[1, 2, 3], &[2, 2, 2]);
+        assert_eq!(dac.widths(), vec![2]);
+        assert!(dac.continues.is_empty());
+        assert_eq!(dac.get(2), 3);
+    }
+}
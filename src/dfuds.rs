@@ -0,0 +1,297 @@
+//! # Dfuds
+//!
+//! An ordinal tree in the depth first unary degree sequence representation as presented by
+//! [Benoit et al.](https://doi.org/10.1007/s00453-004-1146-6) The nodes are visited in depth first
+//! order and each node with `d` children is written as `d` opening parentheses followed by a
+//! closing one. A leading opening parenthesis balances the sequence, so a tree with `n` nodes needs
+//! `2n` bits plus the [`BalancedParentheses`] index.
+//!
+//! In contrast to [`crate::louds::Louds`] the subtree of a node is a contiguous range, so the
+//! `nth` child and the subtree size can be answered in constant time.
+//!
+//! Nodes are identified by the position of their first parenthesis, so the root is always `1`.
+//!
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::balanced_parentheses::BalancedParentheses;
+use crate::space_report::SpaceReport;
+
+pub struct Dfuds {
+    parentheses: BalancedParentheses,
+}
+
+impl Dfuds {
+    /// Builds the tree from the children of each node, where node `0` is the root.
+    ///
+    /// # Panics
+    ///
+    /// If the children do not form a tree in which every node is reached once from the root.
+    pub fn from_children(children: &[Vec<usize>]) -> Self {
+        let mut bit_vec: BitVec<u64, Lsb0> = BitVec::with_capacity(2 * children.len());
+        if !children.is_empty() {
+            bit_vec.push(true);
+            let mut stack = vec![0];
+            let mut visited = 0;
+            while let Some(node) = stack.pop() {
+                // A node which is reached twice would be visited forever on a cycle.
+                visited += 1;
+                assert!(visited <= children.len(), "children do not form a tree");
+                bit_vec.extend(children[node].iter().map(|_| true));
+                bit_vec.push(false);
+                stack.extend(children[node].iter().rev());
+            }
+            assert_eq!(visited, children.len(), "children do not form a tree");
+        }
+        let parentheses = BalancedParentheses::new(bit_vec);
+        assert!(parentheses.is_balanced(), "children do not form a tree");
+        Dfuds { parentheses }
+    }
+
+    /// Builds the tree from the parent of each node, where the root has no parent. The children of
+    /// each node are ordered by their index.
+    ///
+    /// # Panics
+    ///
+    /// If there is not exactly one root, a parent is not a node or the nodes contain a cycle.
+    pub fn from_parents(parents: &[Option<usize>]) -> Self {
+        let mut children = vec![Vec::new(); parents.len()];
+        let mut root = None;
+        for (node, parent) in parents.iter().enumerate() {
+            match parent {
+                Some(parent) => {
+                    assert!(*parent < parents.len(), "parent {parent} is not a node");
+                    children[*parent].push(node);
+                }
+                None => {
+                    assert!(root.is_none(), "more than one root");
+                    root = Some(node);
+                }
+            }
+        }
+        assert!(parents.is_empty() || root.is_some(), "no root");
+        match root {
+            Some(0) | None => Self::from_children(&children),
+            Some(root) => {
+                // Swap the root to the front, as the children are always read starting at node 0.
+                let swap = |node: usize| match node {
+                    0 => root,
+                    node if node == root => 0,
+                    node => node,
+                };
+                children.swap(0, root);
+                for node_children in children.iter_mut() {
+                    node_children
+                        .iter_mut()
+                        .for_each(|child| *child = swap(*child));
+                }
+                Self::from_children(&children)
+            }
+        }
+    }
+
+    /// The number of nodes.
+    pub fn len(&self) -> usize {
+        self.parentheses.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parentheses.is_empty()
+    }
+
+    pub fn root(&self) -> usize {
+        1
+    }
+
+    pub fn parentheses(&self) -> &BalancedParentheses {
+        &self.parentheses
+    }
+
+    pub fn degree(&self, node: usize) -> usize {
+        self.description_end(node) - node
+    }
+
+    pub fn is_leaf(&self, node: usize) -> bool {
+        !self.parentheses.is_open(node)
+    }
+
+    /// The `nth` child of `node`, starting with 0.
+    pub fn child(&self, node: usize, nth: usize) -> Option<usize> {
+        let end = self.description_end(node);
+        if nth >= end - node {
+            return None;
+        }
+        // The subtree of the `nth` child starts behind the match of the `nth` last parenthesis.
+        Some(self.parentheses.find_close(end - nth - 1).unwrap() + 1)
+    }
+
+    pub fn children(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.degree(node)).map(move |nth| self.child(node, nth).unwrap())
+    }
+
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        if node == self.root() {
+            return None;
+        }
+        // The closing parenthesis in front of a node matches the opening one which the parent
+        // wrote for it.
+        let open = self.parentheses.find_open(node - 1).unwrap();
+        Some(self.node(self.preorder(open)))
+    }
+
+    /// The number of nodes in the subtree of `node`, including itself.
+    pub fn subtree_size(&self, node: usize) -> usize {
+        // The subtree ends where the excess drops below the excess in front of the node.
+        let end = self.parentheses.fwd_search(node, -1).unwrap();
+        self.parentheses.bits().rank0(end) - self.preorder(node)
+    }
+
+    /// The position of `node` in a depth first traversal, starting with 0.
+    pub fn preorder(&self, node: usize) -> usize {
+        self.parentheses.bits().rank0(node)
+    }
+
+    /// The node at position `preorder` in a depth first traversal.
+    pub fn node(&self, preorder: usize) -> usize {
+        assert!(preorder < self.len());
+        if preorder == 0 {
+            self.root()
+        } else {
+            self.parentheses.bits().select0(preorder).unwrap() + 1
+        }
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.parentheses.space_usage()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        self.parentheses.space_report()
+    }
+
+    // The position of the closing parenthesis which ends the description of `node`.
+    fn description_end(&self, node: usize) -> usize {
+        let bits = self.parentheses.bits();
+        bits.select0(bits.rank0(node) + 1).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn navigation() {
+        //        0
+        //      / | \
+        //     1  4  5
+        //    / \    |
+        //   2   3   6
+        let children = vec![
+            vec![1, 4, 5],
+            vec![2, 3],
+            vec![],
+            vec![],
+            vec![],
+            vec![6],
+            vec![],
+        ];
+        let dfuds = Dfuds::from_children(&children);
+        assert_eq!(dfuds.len(), 7);
+        let nodes: Vec<usize> = (0..7).map(|preorder| dfuds.node(preorder)).collect();
+        assert_eq!(nodes[0], dfuds.root());
+        assert_eq!(dfuds.degree(nodes[0]), 3);
+        assert_eq!(dfuds.child(nodes[0], 0), Some(nodes[1]));
+        assert_eq!(dfuds.child(nodes[0], 1), Some(nodes[4]));
+        assert_eq!(dfuds.child(nodes[0], 2), Some(nodes[5]));
+        assert_eq!(dfuds.child(nodes[0], 3), None);
+        assert_eq!(
+            dfuds.children(nodes[1]).collect::<Vec<usize>>(),
+            vec![nodes[2], nodes[3]]
+        );
+        assert!(dfuds.is_leaf(nodes[4]));
+        assert_eq!(dfuds.parent(nodes[0]), None);
+        assert_eq!(dfuds.parent(nodes[3]), Some(nodes[1]));
+        assert_eq!(dfuds.parent(nodes[6]), Some(nodes[5]));
+        assert_eq!(dfuds.subtree_size(nodes[0]), 7);
+        assert_eq!(dfuds.subtree_size(nodes[1]), 3);
+        assert_eq!(dfuds.subtree_size(nodes[6]), 1);
+        assert_eq!(dfuds.preorder(nodes[5]), 5);
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = SmallRng::seed_from_u64(9);
+        let mut parents = vec![None];
+        for node in 1..4000usize {
+            parents.push(Some(rng.gen_range(node.saturating_sub(30)..node)));
+        }
+        let dfuds = Dfuds::from_parents(&parents);
+        // The parents are smaller than their children and children are ordered by their index, so
+        // the depth first order is the order of the subtrees.
+        let mut children = vec![Vec::new(); parents.len()];
+        for (node, parent) in parents.iter().enumerate().skip(1) {
+            children[parent.unwrap()].push(node);
+        }
+        let mut preorder = Vec::new();
+        let mut sizes = vec![1; parents.len()];
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            preorder.push(node);
+            stack.extend(children[node].iter().rev());
+        }
+        for node in (1..parents.len()).rev() {
+            sizes[parents[node].unwrap()] += sizes[node];
+        }
+        let mut ranks = vec![0; parents.len()];
+        for (rank, node) in preorder.iter().enumerate() {
+            ranks[*node] = rank;
+        }
+        for (rank, node) in preorder.iter().enumerate() {
+            let position = dfuds.node(rank);
+            assert_eq!(dfuds.preorder(position), rank);
+            assert_eq!(dfuds.degree(position), children[*node].len());
+            assert_eq!(dfuds.subtree_size(position), sizes[*node]);
+            assert_eq!(
+                dfuds.parent(position),
+                parents[*node].map(|parent| dfuds.node(ranks[parent]))
+            );
+            for (nth, child) in children[*node].iter().enumerate() {
+                assert_eq!(dfuds.child(position, nth), Some(dfuds.node(ranks[*child])));
+            }
+        }
+    }
+
+    #[test]
+    fn other_root() {
+        let dfuds = Dfuds::from_parents(&[Some(2), Some(2), None]);
+        assert_eq!(dfuds.len(), 3);
+        assert_eq!(dfuds.degree(dfuds.root()), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "no root")]
+    fn cycle_without_root() {
+        Dfuds::from_parents(&[Some(1), Some(2), Some(0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "children do not form a tree")]
+    fn cycle_beside_root() {
+        Dfuds::from_parents(&[None, Some(2), Some(1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "parent 3 is not a node")]
+    fn parent_out_of_range() {
+        Dfuds::from_parents(&[None, Some(3)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "children do not form a tree")]
+    fn cyclic_children() {
+        Dfuds::from_children(&[vec![1], vec![0]]);
+    }
+}
@@ -0,0 +1,371 @@
+//! # DnaVector
+//!
+//! A sequence of nucleotides with two bits per symbol, packed into `u64` words with 32 symbols
+//! each. Rank and select for each of the four symbols use the same L0, L1 and L2 blocks as the
+//! [`RunawayVector`](crate::runaway_vector::RunawayVector), counted in symbols instead of bits.
+//! The [`InterleavedIndex`] entries of the four symbols for an L1 block are stored next to each
+//! other in a group which is aligned to 64 bytes, so a rank touches a single cache line of the
+//! index.
+//!
+//! Within a word the positions of a symbol are found by comparing both bits of all symbols at once.
+//!
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::{array, cmp, io, mem};
+
+use crate::runaway_vector::{InterleavedIndex, L0_BIT_SIZE, L1_BIT_SIZE, L2_BIT_SIZE};
+use crate::serialize;
+use crate::space_report::{self, SpaceReport};
+
+const SYMBOLS_PER_WORD: usize = 32;
+const L2_IN_L1_COUNT: usize = L1_BIT_SIZE / L2_BIT_SIZE;
+// The lower bit of every symbol in a word.
+const LOW_BITS: u64 = 0x5555_5555_5555_5555;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Nucleotide {
+    A = 0,
+    C = 1,
+    G = 2,
+    T = 3,
+}
+
+impl Nucleotide {
+    pub const ALL: [Nucleotide; 4] = [Nucleotide::A, Nucleotide::C, Nucleotide::G, Nucleotide::T];
+
+    fn from_code(code: u64) -> Self {
+        Self::ALL[code as usize]
+    }
+
+    pub fn as_byte(&self) -> u8 {
+        b"ACGT"[*self as usize]
+    }
+}
+
+impl TryFrom<u8> for Nucleotide {
+    type Error = ();
+
+    /// Parses an upper or lower case nucleotide.
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            b'A' | b'a' => Ok(Nucleotide::A),
+            b'C' | b'c' => Ok(Nucleotide::C),
+            b'G' | b'g' => Ok(Nucleotide::G),
+            b'T' | b't' => Ok(Nucleotide::T),
+            _ => Err(()),
+        }
+    }
+}
+
+// The four entries of a L1 block, one for each symbol, which fill exactly one cache line.
+#[repr(align(64))]
+struct L1Group([InterleavedIndex; 4]);
+
+pub struct DnaVector {
+    words: Vec<u64>,
+    len: usize,
+    l12_indices: Vec<L1Group>,
+    l0_indices: Vec<[u64; 4]>,
+    counts: [usize; 4],
+}
+
+impl DnaVector {
+    pub fn new(symbols: &[Nucleotide]) -> Self {
+        let mut words = vec![0; symbols.len().div_ceil(SYMBOLS_PER_WORD)];
+        for (i, symbol) in symbols.iter().enumerate() {
+            words[i / SYMBOLS_PER_WORD] |= (*symbol as u64) << (2 * (i % SYMBOLS_PER_WORD));
+        }
+        Self::build(words, symbols.len())
+    }
+
+    /// Parses a sequence of upper or lower case nucleotides, or returns `None` if there is any
+    /// other byte.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let symbols = bytes
+            .iter()
+            .map(|byte| Nucleotide::try_from(*byte).ok())
+            .collect::<Option<Vec<Nucleotide>>>()?;
+        Some(Self::new(&symbols))
+    }
+
+    /// Reads the sequences of a FASTA file and concatenates them. Header lines starting with `>`
+    /// and comment lines starting with `;` are skipped, any symbol other than `ACGT` is an error.
+    pub fn from_fasta<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut symbols = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.starts_with('>') || line.starts_with(';') {
+                continue;
+            }
+            for byte in line.bytes() {
+                let symbol = Nucleotide::try_from(byte).map_err(|_| {
+                    serialize::invalid_data(&format!(
+                        "invalid nucleotide {:?} in line {}",
+                        byte as char,
+                        number + 1
+                    ))
+                })?;
+                symbols.push(symbol);
+            }
+        }
+        Ok(Self::new(&symbols))
+    }
+
+    pub fn load_fasta<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_fasta(BufReader::new(File::open(path)?))
+    }
+
+    fn build(words: Vec<u64>, len: usize) -> Self {
+        let mut l0_indices = Vec::with_capacity(len / L0_BIT_SIZE + 1);
+        let mut l12_indices = Vec::with_capacity(len / L1_BIT_SIZE + 1);
+        let mut l0 = [0u64; 4];
+        // Wider than the stored L1 counts, as a whole L0 block of one symbol would overflow them.
+        let mut l1 = [0u64; 4];
+        for l1_start in (0..len).step_by(L1_BIT_SIZE) {
+            if l1_start % L0_BIT_SIZE == 0 {
+                for symbol in 0..4 {
+                    l0[symbol] += l1[symbol];
+                    l1[symbol] = 0;
+                }
+                l0_indices.push(l0);
+            }
+            let l2_count = cmp::min(len - l1_start, L1_BIT_SIZE).div_ceil(L2_BIT_SIZE);
+            let mut l2s = [[0u16; L2_IN_L1_COUNT]; 4];
+            for (l2, l2_start) in (l1_start..len)
+                .step_by(L2_BIT_SIZE)
+                .take(l2_count)
+                .enumerate()
+            {
+                let l2_end = cmp::min(l2_start + L2_BIT_SIZE, len);
+                for symbol in Nucleotide::ALL {
+                    l2s[symbol as usize][l2] = count(&words, symbol, l2_start, l2_end) as u16;
+                }
+            }
+            // Like in the bit vector, the last L2 block of a full L1 block is never needed.
+            let stored = cmp::min(l2_count, L2_IN_L1_COUNT - 1);
+            l12_indices.push(L1Group(array::from_fn(|symbol| {
+                InterleavedIndex::new(l1[symbol] as u32, &l2s[symbol][..stored])
+            })));
+            for symbol in 0..4 {
+                l1[symbol] += l2s[symbol].iter().map(|l2| *l2 as u64).sum::<u64>();
+            }
+        }
+        let mut counts = [0; 4];
+        for symbol in 0..4 {
+            counts[symbol] = (l0[symbol] + l1[symbol]) as usize;
+        }
+        DnaVector {
+            words,
+            len,
+            l12_indices,
+            l0_indices,
+            counts,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of occurrences of `symbol`.
+    pub fn count(&self, symbol: Nucleotide) -> usize {
+        self.counts[symbol as usize]
+    }
+
+    pub fn access(&self, idx: usize) -> Nucleotide {
+        assert!(idx < self.len);
+        let word = self.words[idx / SYMBOLS_PER_WORD];
+        Nucleotide::from_code((word >> (2 * (idx % SYMBOLS_PER_WORD))) & 0b11)
+    }
+
+    /// Counts the occurrences of `symbol` in front of `idx`. `idx` may be the length of the vector.
+    pub fn rank(&self, symbol: Nucleotide, idx: usize) -> usize {
+        assert!(idx <= self.len);
+        if idx == self.len {
+            return self.count(symbol);
+        }
+        let l12 = &self.l12_indices[idx / L1_BIT_SIZE].0[symbol as usize];
+        let l2_pos = (idx / L2_BIT_SIZE) % L2_IN_L1_COUNT;
+        let l2: usize = (0..l2_pos).map(|i| l12.index(i) as usize).sum();
+        let l2_start = idx - idx % L2_BIT_SIZE;
+        self.l0_indices[idx / L0_BIT_SIZE][symbol as usize] as usize
+            + l12.l1() as usize
+            + l2
+            + count(&self.words, symbol, l2_start, idx)
+    }
+
+    /// Finds the position of the `rank`-th occurrence of `symbol`, starting with 1.
+    pub fn select(&self, symbol: Nucleotide, mut rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.count(symbol) {
+            return None;
+        }
+        let s = symbol as usize;
+        let l0_pos = self
+            .l0_indices
+            .partition_point(|l0| (l0[s] as usize) < rank)
+            - 1;
+        rank -= self.l0_indices[l0_pos][s] as usize;
+
+        // The last L1 block in the L0 block which has less than `rank` occurrences in front of it.
+        let first_l1 = l0_pos * (L0_BIT_SIZE / L1_BIT_SIZE);
+        let last_l1 = cmp::min(first_l1 + L0_BIT_SIZE / L1_BIT_SIZE, self.l12_indices.len());
+        let mut l = first_l1;
+        let mut r = last_l1;
+        while l < r {
+            let m = (l + r) / 2;
+            if (self.l12_indices[m].0[s].l1() as usize) < rank {
+                l = m + 1;
+            } else {
+                r = m;
+            }
+        }
+        let l1_pos = l - 1;
+        let l12 = &self.l12_indices[l1_pos].0[s];
+        rank -= l12.l1() as usize;
+
+        let mut pos = l1_pos * L1_BIT_SIZE;
+        for i in 0..l12.len() {
+            if rank > l12.index(i) as usize {
+                rank -= l12.index(i) as usize;
+                pos += L2_BIT_SIZE;
+            } else {
+                break;
+            }
+        }
+
+        let mut word_pos = pos / SYMBOLS_PER_WORD;
+        loop {
+            let matches = matches(self.words[word_pos], symbol);
+            let ones = matches.count_ones() as usize;
+            if rank <= ones {
+                let mut matches = matches;
+                for _ in 1..rank {
+                    matches &= matches - 1;
+                }
+                return Some(word_pos * SYMBOLS_PER_WORD + matches.trailing_zeros() as usize / 2);
+            }
+            rank -= ones;
+            word_pos += 1;
+        }
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.words.len() * 64
+            + space_report::bits(&self.l12_indices)
+            + space_report::bits(&self.l0_indices)
+            + mem::size_of::<Self>()
+    }
+
+    /// The symbols take two bits each, the rest of the last word is padding.
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            data: 2 * self.len,
+            l0: space_report::bits(&self.l0_indices),
+            l12: space_report::bits(&self.l12_indices),
+            padding: space_report::bits(&self.words) - 2 * self.len,
+            slack: space_report::slack(&self.words)
+                + space_report::slack(&self.l0_indices)
+                + space_report::slack(&self.l12_indices),
+            ..SpaceReport::of::<Self>()
+        }
+    }
+}
+
+// The lower bit of every symbol in `word` which equals `symbol`. The unused symbols behind the end
+// of the vector look like `A`, so they must not be counted.
+fn matches(word: u64, symbol: Nucleotide) -> u64 {
+    let diff = word ^ (LOW_BITS * symbol as u64);
+    !(diff | (diff >> 1)) & LOW_BITS
+}
+
+// Counts the occurrences of `symbol` in `start..end`.
+fn count(words: &[u64], symbol: Nucleotide, start: usize, end: usize) -> usize {
+    let mut count = 0;
+    let mut pos = start;
+    while pos < end {
+        let offset = pos % SYMBOLS_PER_WORD;
+        let take = cmp::min(SYMBOLS_PER_WORD - offset, end - pos);
+        let mut word = matches(words[pos / SYMBOLS_PER_WORD], symbol) >> (2 * offset);
+        if take < SYMBOLS_PER_WORD {
+            word &= (1 << (2 * take)) - 1;
+        }
+        count += word.count_ones() as usize;
+        pos += take;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_dna(len: usize, seed: u64) -> Vec<Nucleotide> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        (0..len)
+            .map(|_| Nucleotide::ALL[rng.gen_range(0..4)])
+            .collect()
+    }
+
+    #[test]
+    fn rank_select_access() {
+        for len in [0, 1, 31, 513, 2048, 10_000] {
+            let symbols = random_dna(len, len as u64);
+            let dna = DnaVector::new(&symbols);
+            for symbol in Nucleotide::ALL {
+                let mut rank = 0;
+                for (i, s) in symbols.iter().enumerate() {
+                    assert_eq!(dna.rank(symbol, i), rank);
+                    if *s == symbol {
+                        rank += 1;
+                        assert_eq!(dna.select(symbol, rank), Some(i));
+                    }
+                }
+                assert_eq!(dna.rank(symbol, len), rank);
+                assert_eq!(dna.count(symbol), rank);
+                assert_eq!(dna.select(symbol, rank + 1), None);
+            }
+            for (i, s) in symbols.iter().enumerate() {
+                assert_eq!(dna.access(i), *s);
+            }
+        }
+    }
+
+    #[test]
+    fn from_fasta() {
+        let fasta = b">chr1 test\nACGT\nacgt\n;comment\n>chr2\nTTAA\n";
+        let dna = DnaVector::from_fasta(&fasta[..]).unwrap();
+        assert_eq!(dna.len(), 12);
+        let bytes: Vec<u8> = (0..dna.len()).map(|i| dna.access(i).as_byte()).collect();
+        assert_eq!(bytes, b"ACGTACGTTTAA");
+        assert_eq!(dna.rank(Nucleotide::T, 12), 4);
+
+        let error = DnaVector::from_fasta(&b">chr1\nACGT\nACNT\n"[..])
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 3"));
+        assert!(DnaVector::from_bytes(b"ACGU").is_none());
+        assert_eq!(
+            DnaVector::from_bytes(b"gattaca")
+                .unwrap()
+                .count(Nucleotide::A),
+            3
+        );
+    }
+
+    #[test]
+    fn cache_lines() {
+        assert_eq!(mem::size_of::<L1Group>(), 64);
+        let dna = DnaVector::from_bytes(&b"ACGT".repeat(3000)).unwrap();
+        assert_eq!(dna.l12_indices.as_ptr() as usize % 64, 0);
+    }
+}
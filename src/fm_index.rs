@@ -0,0 +1,365 @@
+//! # FmIndex
+//!
+//! A compressed full-text index as presented by
+//! [Ferragina and Manzini](https://doi.org/10.1109/SFCS.2000.892127). The Burrows-Wheeler
+//! transform of the text is stored in a [`WaveletMatrix`], which allows counting the occurrences
+//! of a pattern with a backward search without decompressing the text.
+//!
+//! Every `sample_rate`-th text position is sampled from the suffix array and its inverse, so
+//! locating an occurrence and extracting text needs at most `sample_rate` LF-steps per position.
+//!
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::{io, mem};
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::runaway_vector::RunawayVector;
+use crate::serialize;
+use crate::space_report::{self, SpaceReport};
+use crate::wavelet_matrix::WaveletMatrix;
+
+pub struct FmIndex {
+    // The BWT without the sentinel, which is the smallest symbol and only occurs once.
+    bwt: WaveletMatrix,
+    // The row of the BWT which holds the sentinel.
+    primary: usize,
+    // The number of symbols in the text which are smaller than each symbol, counting the sentinel.
+    counts: [usize; 257],
+    // Marks the rows whose suffix array value is sampled.
+    sampled: RunawayVector<'static>,
+    sa_samples: Vec<usize>,
+    // The rows of the text positions which are a multiple of the sample rate.
+    isa_samples: Vec<usize>,
+    sample_rate: usize,
+}
+
+impl FmIndex {
+    pub fn new(text: &[u8], sample_rate: usize) -> Self {
+        assert!(sample_rate > 0);
+        let sa = suffix_array(text);
+
+        let mut bwt = Vec::with_capacity(text.len());
+        let mut primary = 0;
+        let mut sampled = BitVec::with_capacity(sa.len());
+        let mut sa_samples = Vec::with_capacity(sa.len() / sample_rate + 1);
+        let mut isa_samples = vec![0; text.len() / sample_rate + 1];
+        for (row, pos) in sa.iter().enumerate() {
+            if *pos == 0 {
+                primary = row;
+            } else {
+                bwt.push(text[pos - 1]);
+            }
+            let is_sampled = pos % sample_rate == 0;
+            sampled.push(is_sampled);
+            if is_sampled {
+                sa_samples.push(*pos);
+                // The row of the sentinel suffix is always 0 and does not need a sample.
+                if *pos < text.len() {
+                    isa_samples[pos / sample_rate] = row;
+                }
+            }
+        }
+        Self::from_parts(&bwt, primary, sampled, sa_samples, isa_samples, sample_rate)
+    }
+
+    fn from_parts(
+        bwt: &[u8],
+        primary: usize,
+        sampled: BitVec<u64, Lsb0>,
+        sa_samples: Vec<usize>,
+        isa_samples: Vec<usize>,
+        sample_rate: usize,
+    ) -> Self {
+        let mut counts = [0; 257];
+        for symbol in bwt {
+            counts[*symbol as usize + 1] += 1;
+        }
+        // The sentinel is smaller than every other symbol.
+        counts[0] = 1;
+        for i in 1..counts.len() {
+            counts[i] += counts[i - 1];
+        }
+        FmIndex {
+            bwt: WaveletMatrix::new(bwt),
+            primary,
+            counts,
+            sampled: RunawayVector::from_owned(sampled),
+            sa_samples,
+            isa_samples,
+            sample_rate,
+        }
+    }
+
+    /// The length of the indexed text.
+    pub fn len(&self) -> usize {
+        self.bwt.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bwt.is_empty()
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    /// Counts the occurrences of `pattern` in the text.
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        self.search(pattern).len()
+    }
+
+    /// Finds the starting positions of all occurrences of `pattern` in ascending order.
+    pub fn locate(&self, pattern: &[u8]) -> Vec<usize> {
+        let mut positions: Vec<usize> = self
+            .search(pattern)
+            .map(|row| self.locate_row(row))
+            .collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    /// Extracts `range` of the text.
+    pub fn extract(&self, range: Range<usize>) -> Vec<u8> {
+        assert!(range.start <= range.end && range.end <= self.len());
+        if range.is_empty() {
+            return Vec::new();
+        }
+        // Start at the first sampled position at or after the end of the range and walk backwards.
+        let mut pos = range.end.div_ceil(self.sample_rate) * self.sample_rate;
+        let mut row = if pos >= self.len() {
+            pos = self.len();
+            0
+        } else {
+            self.isa_samples[pos / self.sample_rate]
+        };
+        let mut extracted = Vec::with_capacity(range.len());
+        while pos > range.start {
+            // The BWT holds the symbol in front of the suffix of each row.
+            if pos <= range.end {
+                extracted.push(self.bwt_symbol(row));
+            }
+            row = self.lf(row);
+            pos -= 1;
+        }
+        extracted.reverse();
+        extracted
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.bwt.space_usage()
+            + self.sampled.len()
+            + self.sampled.space_usage()
+            + (self.sa_samples.len() + self.isa_samples.len() + self.counts.len()) * 64
+            + mem::size_of::<Self>()
+    }
+
+    /// The BWT is the data, the samples of the suffix array and its inverse are other support.
+    /// The counts of the symbols are part of the struct.
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            other: space_report::bits(&self.sa_samples) + space_report::bits(&self.isa_samples),
+            slack: space_report::slack(&self.sa_samples) + space_report::slack(&self.isa_samples),
+            ..SpaceReport::of::<Self>()
+        } + self.bwt.space_report().inline()
+            + self.sampled.space_report().inline()
+    }
+
+    /// Writes the BWT and the samples. The rank and select indices are rebuilt when reading.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let bwt: Vec<u8> = (0..self.bwt.len()).map(|i| self.bwt.access(i)).collect();
+        serialize::write_u64(writer, self.sample_rate as u64)?;
+        serialize::write_u64(writer, self.primary as u64)?;
+        serialize::write_bytes(writer, &bwt)?;
+        serialize::write_bit_vec(writer, self.sampled.bit_vec())?;
+        serialize::write_u64s(writer, &to_u64s(&self.sa_samples))?;
+        serialize::write_u64s(writer, &to_u64s(&self.isa_samples))
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let sample_rate = serialize::read_usize(reader)?;
+        let primary = serialize::read_usize(reader)?;
+        let bwt = serialize::read_bytes(reader)?;
+        let sampled = serialize::read_bit_vec(reader)?;
+        let sa_samples = from_u64s(serialize::read_u64s(reader)?)?;
+        let isa_samples = from_u64s(serialize::read_u64s(reader)?)?;
+        if sample_rate == 0
+            || primary > bwt.len()
+            || sampled.len() != bwt.len() + 1
+            || sampled.count_ones() != sa_samples.len()
+            || isa_samples.len() != bwt.len() / sample_rate + 1
+        {
+            return Err(serialize::invalid_data("inconsistent FM-index"));
+        }
+        Ok(Self::from_parts(
+            &bwt,
+            primary,
+            sampled,
+            sa_samples,
+            isa_samples,
+            sample_rate,
+        ))
+    }
+
+    // Backward search for the rows whose suffixes start with `pattern`.
+    fn search(&self, pattern: &[u8]) -> Range<usize> {
+        // Skip the row of the sentinel suffix, so the empty pattern matches every text position.
+        if pattern.is_empty() {
+            return 1..self.len() + 1;
+        }
+        let mut start = 0;
+        let mut end = self.len() + 1;
+        for symbol in pattern.iter().rev() {
+            start = self.counts[*symbol as usize] + self.occ(*symbol, start);
+            end = self.counts[*symbol as usize] + self.occ(*symbol, end);
+            if start >= end {
+                return 0..0;
+            }
+        }
+        start..end
+    }
+
+    fn locate_row(&self, mut row: usize) -> usize {
+        let mut steps = 0;
+        while !self.sampled.access(row) {
+            row = self.lf(row);
+            steps += 1;
+        }
+        self.sa_samples[self.sampled.rank1(row)] + steps
+    }
+
+    // Maps a row to the row of the suffix which starts one position earlier in the text.
+    fn lf(&self, row: usize) -> usize {
+        if row == self.primary {
+            return 0;
+        }
+        let symbol = self.bwt_symbol(row);
+        self.counts[symbol as usize] + self.occ(symbol, row)
+    }
+
+    fn bwt_symbol(&self, row: usize) -> u8 {
+        debug_assert!(row != self.primary);
+        self.bwt.access(self.bwt_pos(row))
+    }
+
+    // Counts the occurrences of `symbol` in the rows in front of `row`.
+    fn occ(&self, symbol: u8, row: usize) -> usize {
+        self.bwt.rank(symbol, self.bwt_pos(row))
+    }
+
+    // The BWT does not store the sentinel, so all rows behind it are shifted by one.
+    fn bwt_pos(&self, row: usize) -> usize {
+        if row > self.primary {
+            row - 1
+        } else {
+            row
+        }
+    }
+}
+
+/// Sorts the suffixes of `text` followed by a sentinel with prefix doubling in `O(n log² n)`.
+fn suffix_array(text: &[u8]) -> Vec<usize> {
+    let n = text.len() + 1;
+    let mut sa: Vec<usize> = (0..n).collect();
+    // The sentinel gets the smallest rank.
+    let mut ranks: Vec<usize> = text.iter().map(|symbol| *symbol as usize + 1).collect();
+    ranks.push(0);
+    let mut next_ranks = vec![0; n];
+    let mut k = 1;
+    loop {
+        let key = |i: usize| (ranks[i], ranks.get(i + k).map_or(0, |rank| rank + 1));
+        sa.sort_unstable_by_key(|i| key(*i));
+        next_ranks[sa[0]] = 0;
+        for i in 1..n {
+            next_ranks[sa[i]] = next_ranks[sa[i - 1]] + (key(sa[i - 1]) != key(sa[i])) as usize;
+        }
+        mem::swap(&mut ranks, &mut next_ranks);
+        if ranks[sa[n - 1]] == n - 1 {
+            return sa;
+        }
+        k *= 2;
+    }
+}
+
+fn to_u64s(values: &[usize]) -> Vec<u64> {
+    values.iter().map(|value| *value as u64).collect()
+}
+
+fn from_u64s(values: Vec<u64>) -> io::Result<Vec<usize>> {
+    values
+        .into_iter()
+        .map(|value| usize::try_from(value).map_err(|_| serialize::invalid_data("value too large")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"abracadabra, the abracadabra of banana bandanas";
+
+    fn naive_locate(text: &[u8], pattern: &[u8]) -> Vec<usize> {
+        (0..=text.len() - pattern.len())
+            .filter(|i| text[*i..].starts_with(pattern))
+            .collect()
+    }
+
+    #[test]
+    fn suffix_array_banana() {
+        assert_eq!(suffix_array(b"banana"), vec![6, 5, 3, 1, 0, 4, 2]);
+        assert_eq!(suffix_array(b""), vec![0]);
+    }
+
+    #[test]
+    fn count_and_locate() {
+        for sample_rate in [1, 3, 32] {
+            let index = FmIndex::new(TEXT, sample_rate);
+            for pattern in [
+                &b"abra"[..],
+                b"a",
+                b"an",
+                b"bandanas",
+                b"xyz",
+                b"s",
+                b"abracadabra,",
+            ] {
+                let expected = naive_locate(TEXT, pattern);
+                assert_eq!(index.count(pattern), expected.len());
+                assert_eq!(index.locate(pattern), expected);
+            }
+            assert_eq!(index.count(b""), TEXT.len());
+        }
+    }
+
+    #[test]
+    fn extract() {
+        for sample_rate in [1, 4, 100] {
+            let index = FmIndex::new(TEXT, sample_rate);
+            assert_eq!(index.extract(0..TEXT.len()), TEXT);
+            for start in 0..TEXT.len() {
+                assert_eq!(index.extract(start..start + 1), &TEXT[start..start + 1]);
+            }
+            assert_eq!(index.extract(5..17), &TEXT[5..17]);
+            assert!(index.extract(3..3).is_empty());
+        }
+    }
+
+    #[test]
+    fn empty_text() {
+        let index = FmIndex::new(b"", 4);
+        assert!(index.is_empty());
+        assert_eq!(index.count(b"a"), 0);
+        assert!(index.locate(b"a").is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let index = FmIndex::new(TEXT, 5);
+        let mut buffer = Vec::new();
+        index.write_to(&mut buffer).unwrap();
+        let read = FmIndex::read_from(&mut buffer.as_slice()).unwrap();
+        assert_eq!(read.locate(b"ban"), index.locate(b"ban"));
+        assert_eq!(read.extract(10..30), &TEXT[10..30]);
+    }
+}
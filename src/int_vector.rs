@@ -0,0 +1,245 @@
+//! # IntVector
+//!
+//! An array of unsigned integers which all use the same number of bits, chosen at runtime between
+//! 1 and 64. The integers are packed back to back into a bit vector, so an integer may span two
+//! words. On disk the vector is its width followed by the packed bits in the format of
+//! [`serialize::write_bit_vec`].
+//!
+use std::io::{Read, Write};
+use std::{io, mem};
+
+use bitvec::field::BitField;
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::serialize;
+use crate::space_report::{self, SpaceReport};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntVector {
+    width: usize,
+    bits: BitVec<u64, Lsb0>,
+}
+
+impl IntVector {
+    pub fn new(width: usize) -> Self {
+        Self::with_capacity(width, 0)
+    }
+
+    pub fn with_capacity(width: usize, capacity: usize) -> Self {
+        assert!((1..=64).contains(&width), "width must be between 1 and 64");
+        IntVector {
+            width,
+            bits: BitVec::with_capacity(width * capacity),
+        }
+    }
+
+    /// Builds the vector with the smallest width which fits the largest of `values`.
+    pub fn from_slice(values: &[u64]) -> Self {
+        let max = values.iter().copied().max().unwrap_or(0);
+        let mut int_vector = Self::with_capacity(width_of(max), values.len());
+        int_vector.extend(values.iter().copied());
+        int_vector
+    }
+
+    /// The number of integers.
+    pub fn len(&self) -> usize {
+        self.bits.len() / self.width
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// The number of bits of each integer.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn bits(&self) -> &BitVec<u64, Lsb0> {
+        &self.bits
+    }
+
+    pub fn get(&self, idx: usize) -> u64 {
+        assert!(idx < self.len());
+        self.bits[idx * self.width..(idx + 1) * self.width].load_le()
+    }
+
+    pub fn set(&mut self, idx: usize, value: u64) {
+        assert!(idx < self.len());
+        assert!(
+            width_of(value) <= self.width,
+            "value does not fit into the width"
+        );
+        self.bits[idx * self.width..(idx + 1) * self.width].store_le(value);
+    }
+
+    pub fn push(&mut self, value: u64) {
+        // Checked before growing, so a value which does not fit leaves the vector unchanged.
+        assert!(
+            width_of(value) <= self.width,
+            "value does not fit into the width"
+        );
+        let start = self.bits.len();
+        self.bits.resize(start + self.width, false);
+        self.set(start / self.width, value);
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            int_vector: self,
+            front: 0,
+            back: self.len(),
+        }
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.bits.len() + mem::size_of::<Self>()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport::of::<Self>() + space_report::bit_vec(&self.bits)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_u64(writer, self.width as u64)?;
+        serialize::write_bit_vec(writer, &self.bits)
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let width = serialize::read_usize(reader)?;
+        let bits = serialize::read_bit_vec(reader)?;
+        if !(1..=64).contains(&width) || bits.len() % width != 0 {
+            return Err(serialize::invalid_data("inconsistent integer vector"));
+        }
+        Ok(IntVector { width, bits })
+    }
+}
+
+/// The number of bits needed to store `value`, but at least one.
+pub fn width_of(value: u64) -> usize {
+    (u64::BITS - value.leading_zeros()).max(1) as usize
+}
+
+impl Extend<u64> for IntVector {
+    fn extend<I: IntoIterator<Item = u64>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a IntVector {
+    type Item = u64;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct Iter<'a> {
+    int_vector: &'a IntVector,
+    front: usize,
+    back: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        Some(self.int_vector.get(self.front - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.back - self.front, Some(self.back - self.front))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.int_vector.get(self.back))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn get_set_push() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        for width in 1..=64 {
+            let max = u64::MAX >> (64 - width);
+            let values: Vec<u64> = (0..200).map(|_| rng.gen_range(0..=max)).collect();
+            let mut int_vector = IntVector::new(width);
+            int_vector.extend(values.iter().copied());
+            assert_eq!(int_vector.len(), values.len());
+            assert_eq!(int_vector.iter().collect::<Vec<u64>>(), values);
+            int_vector.set(77, max);
+            assert_eq!(int_vector.get(77), max);
+            assert_eq!(int_vector.get(76), values[76]);
+            assert_eq!(int_vector.get(78), values[78]);
+        }
+    }
+
+    #[test]
+    fn from_slice() {
+        assert_eq!(IntVector::from_slice(&[]).width(), 1);
+        assert_eq!(IntVector::from_slice(&[0, 1]).width(), 1);
+        assert_eq!(IntVector::from_slice(&[3, 1000]).width(), 10);
+        assert_eq!(IntVector::from_slice(&[u64::MAX]).width(), 64);
+        let int_vector = IntVector::from_slice(&[5, 0, 7, 2]);
+        assert_eq!(
+            int_vector.iter().rev().collect::<Vec<u64>>(),
+            vec![2, 7, 0, 5]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_wide() {
+        IntVector::new(3).push(8);
+    }
+
+    #[test]
+    fn too_wide_unchanged() {
+        let mut int_vector = IntVector::new(3);
+        int_vector.push(5);
+        let pushed = panic::catch_unwind(AssertUnwindSafe(|| int_vector.push(8)));
+        assert!(pushed.is_err());
+        assert_eq!(int_vector.len(), 1);
+        assert_eq!(int_vector.get(0), 5);
+    }
+
+    #[test]
+    fn round_trip() {
+        let values: Vec<u64> = (0..1000).map(|i| i * 7 % 513).collect();
+        let int_vector = IntVector::from_slice(&values);
+        let mut buffer = Vec::new();
+        int_vector.write_to(&mut buffer).unwrap();
+        let mut reader = buffer.as_slice();
+        assert_eq!(serialize::read_u64(&mut reader).unwrap(), 10);
+        assert_eq!(
+            serialize::read_bit_vec(&mut reader).unwrap(),
+            *int_vector.bits()
+        );
+        assert_eq!(
+            IntVector::read_from(&mut buffer.as_slice()).unwrap(),
+            int_vector
+        );
+    }
+}
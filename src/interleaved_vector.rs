@@ -0,0 +1,246 @@
+//! # InterleavedVector
+//!
+//! A bit vector with rank and select support in a fully interleaved layout. Each cache line holds
+//! the number of ones in front of it in its first word, followed by 448 bits in the other seven
+//! words. A rank therefore reads a single cache line, where a
+//! [`crate::runaway_vector::RunawayVector`] reads the line of its index and the line of the bits.
+//! The price is an overhead of one word per seven words of bits.
+//!
+//! The lines are stored in [`AlignedWords`], so they start at a cache line and can be backed by
+//! huge pages. A select searches the counts of the lines with a binary search and scans the seven
+//! words of the line like a select on a `RunawayVector` does.
+//!
+use std::mem;
+
+use bitvec::field::BitField;
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::aligned::{AlignedWords, Pages, CACHE_LINE_WORDS};
+use crate::rank_select::RankSelect;
+use crate::scan;
+use crate::space_report::SpaceReport;
+use crate::word::select_in_word;
+
+const DATA_WORDS: usize = CACHE_LINE_WORDS - 1;
+pub const LINE_BIT_SIZE: usize = DATA_WORDS * 64;
+
+pub struct InterleavedVector {
+    lines: AlignedWords,
+    len: usize,
+    ones: usize,
+}
+
+impl InterleavedVector {
+    pub fn new(bit_vec: &BitVec<u64, Lsb0>, pages: Pages) -> Self {
+        let line_count = bit_vec.len().div_ceil(LINE_BIT_SIZE);
+        let mut lines = AlignedWords::zeroed(line_count * CACHE_LINE_WORDS, pages);
+        let mut ones = 0;
+        // The words are loaded by the positions of their bits rather than copied from the raw
+        // words, so bits which start inside their first word are moved to the start of the line
+        // and the unused bits behind the end of the vector stay cleared.
+        let mut words = bit_vec.chunks(64).map(|chunk| chunk.load_le::<u64>());
+        for line in lines.chunks_exact_mut(CACHE_LINE_WORDS) {
+            line[0] = ones as u64;
+            for (data, word) in line[1..].iter_mut().zip(words.by_ref()) {
+                *data = word;
+                ones += word.count_ones() as usize;
+            }
+        }
+
+        InterleavedVector {
+            lines,
+            len: bit_vec.len(),
+            ones,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the lines are backed by huge pages, see [`AlignedWords::is_mapped`].
+    pub fn is_mapped(&self) -> bool {
+        self.lines.is_mapped()
+    }
+
+    // The index of the word holding the bit `idx`.
+    fn word_of(idx: usize) -> usize {
+        (idx / LINE_BIT_SIZE) * CACHE_LINE_WORDS + 1 + (idx % LINE_BIT_SIZE) / 64
+    }
+
+    pub fn access(&self, idx: usize) -> bool {
+        assert!(idx < self.len);
+        (self.lines[Self::word_of(idx)] >> (idx % 64)) & 1 == 1
+    }
+
+    pub fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank1(idx)
+    }
+
+    /// Counts the ones in front of `idx`, which may be the length of the vector, by reading the
+    /// cache line of `idx` only.
+    pub fn rank1(&self, idx: usize) -> usize {
+        assert!(idx <= self.len);
+        if idx == self.len {
+            return self.ones;
+        }
+        let first = (idx / LINE_BIT_SIZE) * CACHE_LINE_WORDS;
+        let word = Self::word_of(idx);
+        let in_front: usize = self.lines[first + 1..word]
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum();
+        let mask = (1 << (idx % 64)) - 1;
+        self.lines[first] as usize + in_front + (self.lines[word] & mask).count_ones() as usize
+    }
+
+    pub fn select0(&self, rank: usize) -> Option<usize> {
+        self.select(rank, false)
+    }
+
+    pub fn select1(&self, rank: usize) -> Option<usize> {
+        self.select(rank, true)
+    }
+
+    // The ones or zeros in front of `line`.
+    fn before(&self, line: usize, bit: bool) -> usize {
+        let ones = self.lines[line * CACHE_LINE_WORDS] as usize;
+        match bit {
+            true => ones,
+            false => line * LINE_BIT_SIZE - ones,
+        }
+    }
+
+    fn select(&self, rank: usize, bit: bool) -> Option<usize> {
+        assert!(rank > 0);
+        let total = match bit {
+            true => self.ones,
+            false => self.len - self.ones,
+        };
+        if rank > total {
+            return None;
+        }
+        // The last line with fewer ones or zeros in front of it than the rank.
+        let mut l = 0;
+        let mut r = self.lines.len() / CACHE_LINE_WORDS - 1;
+        while l < r {
+            let m = (l + r).div_ceil(2);
+            if self.before(m, bit) < rank {
+                l = m;
+            } else {
+                r = m - 1;
+            }
+        }
+        let mut words = [0; CACHE_LINE_WORDS];
+        for (k, word) in words.iter_mut().take(DATA_WORDS).enumerate() {
+            let idx = l * LINE_BIT_SIZE + 64 * k;
+            if idx < self.len {
+                let data = self.lines[l * CACHE_LINE_WORDS + 1 + k];
+                let data = if bit { data } else { !data };
+                *word = data & (u64::MAX >> (64 - (self.len - idx).min(64)));
+            }
+        }
+        let (k, rank) = scan::word_in_block(&words, rank - self.before(l, bit))?;
+        Some(l * LINE_BIT_SIZE + 64 * k + select_in_word(words[k], rank))
+    }
+
+    /// The space used in bits by the counts of the lines and the padding of the last line, but not
+    /// by the bits themselves.
+    pub fn space_usage(&self) -> usize {
+        self.lines.len() * 64 - self.len + mem::size_of::<Self>()
+    }
+
+    /// The counts of the lines as L0 counters and the unused bits of the last line as padding.
+    pub fn space_report(&self) -> SpaceReport {
+        let counts = self.lines.len() / CACHE_LINE_WORDS * 64;
+        SpaceReport {
+            data: self.len,
+            l0: counts,
+            padding: self.lines.len() * 64 - counts - self.len,
+            ..SpaceReport::of::<Self>()
+        }
+    }
+}
+
+impl RankSelect for InterleavedVector {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn access(&self, idx: usize) -> bool {
+        self.access(idx)
+    }
+
+    fn rank0(&self, idx: usize) -> usize {
+        self.rank0(idx)
+    }
+
+    fn rank1(&self, idx: usize) -> usize {
+        self.rank1(idx)
+    }
+
+    fn select0(&self, rank: usize) -> Option<usize> {
+        self.select0(rank)
+    }
+
+    fn select1(&self, rank: usize) -> Option<usize> {
+        self.select1(rank)
+    }
+
+    fn space_usage(&self) -> usize {
+        self.space_usage()
+    }
+
+    fn space_report(&self) -> SpaceReport {
+        self.space_report()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rank_select::tests::{random_bit_vec, same_as_runaway};
+
+    #[test]
+    fn random() {
+        for (len, density, pages) in [
+            (1, 0.5, Pages::Normal),
+            (448, 0.5, Pages::Normal),
+            (5000, 0.5, Pages::Huge),
+            (20_000, 0.03, Pages::Normal),
+            (20_000, 0.98, Pages::Huge),
+        ] {
+            let bit_vec = random_bit_vec(len, density, len as u64);
+            same_as_runaway(&InterleavedVector::new(&bit_vec, pages), &bit_vec);
+        }
+    }
+
+    #[test]
+    fn unused_bits() {
+        let mut bit_vec: BitVec<u64, Lsb0> = BitVec::repeat(true, 500);
+        bit_vec.truncate(450);
+        let interleaved = InterleavedVector::new(&bit_vec, Pages::Normal);
+        assert_eq!(interleaved.rank1(450), 450);
+        assert_eq!(interleaved.select0(1), None);
+        assert!(InterleavedVector::new(&BitVec::new(), Pages::Huge).is_empty());
+    }
+
+    #[test]
+    fn unaligned() {
+        let base: BitVec<u64, Lsb0> = (0..2005).map(|i| (i * 7 + i / 300) % 3 == 0).collect();
+        let bit_vec = base[5..].to_bitvec();
+        let interleaved = InterleavedVector::new(&bit_vec, Pages::Normal);
+        for i in 0..bit_vec.len() {
+            assert_eq!(interleaved.access(i), bit_vec[i]);
+            assert_eq!(interleaved.rank1(i), bit_vec[..i].count_ones());
+        }
+        for (rank, idx) in bit_vec.iter_ones().enumerate() {
+            assert_eq!(interleaved.select1(rank + 1), Some(idx));
+        }
+    }
+}
@@ -1,3 +1,27 @@
+pub mod aligned;
+pub mod balanced_parentheses;
+pub mod bit_ops;
+pub mod bp_tree;
+pub mod dac;
+pub mod dfuds;
+pub mod dna_vector;
+pub mod fm_index;
+pub mod int_vector;
+pub mod interleaved_vector;
+pub mod louds;
 pub mod query;
+pub mod rank9;
+pub mod rank_select;
+pub mod rle_vector;
+pub mod roaring;
+pub mod rmq;
+pub mod rrr_vector;
+mod scan;
 pub mod runaway_vector;
 pub mod naive_vector;
+pub mod serialize;
+pub mod space_report;
+pub mod sparse_vector;
+pub mod trie;
+pub mod wavelet_matrix;
+mod word;
@@ -0,0 +1,262 @@
+//! # Louds
+//!
+//! An ordinal tree in the level-order unary degree sequence representation as presented by
+//! [Jacobson](https://doi.org/10.1109/SFCS.1989.63533). The nodes are visited in breadth first
+//! order and each node with `d` children is written as `d` ones followed by a zero. A leading `10`
+//! stands for a super root above the root, so a tree with `n` nodes needs `2n + 1` bits.
+//!
+//! Nodes are identified by their position in the breadth first order, so the root is `0` and the
+//! children of each node have consecutive ids. Labels can be attached by storing them in an array
+//! indexed by these ids.
+//!
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::{BitSlice, BitVec};
+
+use crate::runaway_vector::RunawayVector;
+use crate::space_report::SpaceReport;
+
+pub struct Louds<'a> {
+    bits: RunawayVector<'a>,
+}
+
+impl<'a> Louds<'a> {
+    /// Builds the tree from its bits, including the super root.
+    pub fn new(bit_vec: BitVec<u64, Lsb0>) -> Louds<'static> {
+        assert!(Self::is_valid(&bit_vec), "bits do not form a tree");
+        Louds {
+            bits: RunawayVector::from_owned(bit_vec),
+        }
+    }
+
+    /// Builds the tree from the first `len` bits of `words`, which are not copied if they are
+    /// borrowed, see [`RunawayVector::from_words`].
+    pub fn from_words(words: Cow<'a, [u64]>, len: usize) -> Self {
+        assert!(
+            words.len() == len.div_ceil(64) && Self::is_valid(&BitSlice::from_slice(&words)[..len]),
+            "bits do not form a tree"
+        );
+        Louds {
+            bits: RunawayVector::from_words(words, len),
+        }
+    }
+
+    /// Whether `bits` encode a tree including the super root. Besides one more zero than ones, this
+    /// needs the super root to have a single child and each node to be a child of an earlier one.
+    pub fn is_valid(bits: &BitSlice<u64, Lsb0>) -> bool {
+        if bits.is_empty() {
+            return true;
+        }
+        let nodes = bits.len() / 2;
+        let (mut ones, mut zeros) = (0, 0);
+        for bit in bits.iter().by_vals() {
+            if bit {
+                ones += 1;
+                continue;
+            }
+            // Zero `j` is followed by the children of node `j`, which must be a child of an earlier
+            // node. The first zero ends the super root, whose only child is the root.
+            let valid = match zeros {
+                0 => ones == 1,
+                _ => zeros == nodes || ones > zeros,
+            };
+            if !valid {
+                return false;
+            }
+            zeros += 1;
+        }
+        ones == nodes && zeros == nodes + 1
+    }
+
+    /// Builds the tree from the number of children of each node in breadth first order.
+    pub fn from_degrees(degrees: &[usize]) -> Louds<'static> {
+        assert!(
+            degrees.is_empty() || degrees.iter().sum::<usize>() == degrees.len() - 1,
+            "degrees do not form a tree"
+        );
+        let mut bit_vec: BitVec<u64, Lsb0> = BitVec::with_capacity(2 * degrees.len() + 1);
+        if !degrees.is_empty() {
+            bit_vec.extend([true, false]);
+        }
+        for degree in degrees {
+            bit_vec.extend((0..*degree).map(|_| true));
+            bit_vec.push(false);
+        }
+        Louds::new(bit_vec)
+    }
+
+    /// Builds the tree from the children of each node, where node `0` is the root. Returns the tree
+    /// and the original node of each id.
+    pub fn from_children(children: &[Vec<usize>]) -> (Louds<'static>, Vec<usize>) {
+        let mut order = Vec::with_capacity(children.len());
+        let mut degrees = Vec::with_capacity(children.len());
+        let mut queue = VecDeque::new();
+        if !children.is_empty() {
+            queue.push_back(0);
+        }
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            degrees.push(children[node].len());
+            queue.extend(children[node].iter());
+        }
+        (Louds::from_degrees(&degrees), order)
+    }
+
+    /// The number of nodes.
+    pub fn len(&self) -> usize {
+        self.bits.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    pub fn bits(&self) -> &RunawayVector<'a> {
+        &self.bits
+    }
+
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        assert!(node < self.len());
+        if node == 0 {
+            return None;
+        }
+        // The one of a node is written by its parent, and each zero in front of it ends one node or
+        // the super root.
+        let one = self.bits.select1(node + 1).unwrap();
+        Some(self.bits.rank0(one) - 1)
+    }
+
+    /// The ids of the children of `node`.
+    pub fn children(&self, node: usize) -> Range<usize> {
+        let (start, end) = self.unary(node);
+        // Each one in front of the children stands for one node, starting with the root.
+        let first = self.bits.rank1(start);
+        first..first + (end - start)
+    }
+
+    /// The `nth` child of `node`, starting with 0.
+    pub fn child(&self, node: usize, nth: usize) -> Option<usize> {
+        let children = self.children(node);
+        Some(children.start + nth).filter(|child| children.contains(child))
+    }
+
+    pub fn degree(&self, node: usize) -> usize {
+        let (start, end) = self.unary(node);
+        end - start
+    }
+
+    pub fn is_leaf(&self, node: usize) -> bool {
+        self.degree(node) == 0
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.bits.len() + self.bits.space_usage()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        self.bits.space_report()
+    }
+
+    // The range of the ones which encode the children of `node`.
+    fn unary(&self, node: usize) -> (usize, usize) {
+        assert!(node < self.len());
+        let start = self.bits.select0(node + 1).unwrap() + 1;
+        let end = self.bits.select0(node + 2).unwrap();
+        (start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn navigation() {
+        //        0
+        //      / | \
+        //     1  2  3
+        //    / \    |
+        //   4   5   6
+        let louds = Louds::from_degrees(&[3, 2, 0, 1, 0, 0, 0]);
+        assert_eq!(louds.len(), 7);
+        assert_eq!(louds.bits().len(), 15);
+        assert_eq!(louds.children(0), 1..4);
+        assert_eq!(louds.children(1), 4..6);
+        assert_eq!(louds.children(2), 6..6);
+        assert_eq!(louds.children(3), 6..7);
+        assert_eq!(louds.child(3, 0), Some(6));
+        assert_eq!(louds.child(3, 1), None);
+        assert_eq!(louds.degree(0), 3);
+        assert!(louds.is_leaf(2));
+        assert!(!louds.is_leaf(3));
+        assert_eq!(louds.parent(0), None);
+        assert_eq!(louds.parent(5), Some(1));
+        assert_eq!(louds.parent(6), Some(3));
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = SmallRng::seed_from_u64(5);
+        let mut children = vec![Vec::new()];
+        let mut parents = vec![0];
+        for node in 1..5000usize {
+            let parent = rng.gen_range(node.saturating_sub(20)..node);
+            children[parent].push(node);
+            children.push(Vec::new());
+            parents.push(parent);
+        }
+        let (louds, order) = Louds::from_children(&children);
+        let mut ids = vec![0; order.len()];
+        for (id, node) in order.iter().enumerate() {
+            ids[*node] = id;
+        }
+        for (id, node) in order.iter().enumerate() {
+            assert_eq!(louds.degree(id), children[*node].len());
+            let expected: Vec<usize> = children[*node].iter().map(|child| ids[*child]).collect();
+            assert_eq!(louds.children(id).collect::<Vec<usize>>(), expected);
+            if *node != 0 {
+                assert_eq!(louds.parent(id), Some(ids[parents[*node]]));
+            }
+        }
+    }
+
+    #[test]
+    fn validity() {
+        let bits = |bits: &[u8]| {
+            bits.iter()
+                .map(|bit| *bit == 1)
+                .collect::<BitVec<u64, Lsb0>>()
+        };
+        assert!(Louds::is_valid(&bits(&[])));
+        assert!(Louds::is_valid(&bits(&[1, 0, 0])));
+        assert!(Louds::is_valid(&bits(&[1, 0, 1, 1, 0, 0, 0])));
+        // Two roots, a node without a parent and a lone super root.
+        assert!(!Louds::is_valid(&bits(&[1, 1, 0, 0, 0])));
+        assert!(!Louds::is_valid(&bits(&[1, 0, 0, 1, 0])));
+        assert!(!Louds::is_valid(&bits(&[1, 0])));
+        assert!(!Louds::is_valid(&bits(&[0, 1, 0])));
+    }
+
+    #[test]
+    #[should_panic(expected = "bits do not form a tree")]
+    fn invalid_bits() {
+        Louds::new(BitVec::repeat(false, 3));
+    }
+
+    #[test]
+    fn single_node() {
+        let louds = Louds::from_degrees(&[0]);
+        assert_eq!(louds.len(), 1);
+        assert!(louds.is_leaf(0));
+        assert_eq!(louds.parent(0), None);
+    }
+}
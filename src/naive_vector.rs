@@ -2,7 +2,8 @@ use std::{cmp, mem};
 use bitvec::order::Lsb0;
 use bitvec::prelude::BitVec;
 use crate::query::{Query, QueryResult};
-use crate::query::Query::{Access, Rank, Select};
+use crate::rank_select::RankSelect;
+use crate::space_report::{self, SpaceReport};
 
 pub struct NaiveVector<'a> {
     bit_vec: &'a BitVec<u64, Lsb0>,
@@ -28,17 +29,22 @@ impl<'a> NaiveVector<'a> {
     }
 
     pub fn rank0(&self, idx: usize) -> usize {
-        assert!(idx < self.bit_vec.len());
+        assert!(idx <= self.bit_vec.len());
         idx - self.rank1(idx)
     }
 
     pub fn rank1(&self, idx: usize) -> usize {
-        assert!(idx < self.bit_vec.len());
+        assert!(idx <= self.bit_vec.len());
         let block_pos = idx / self.block_size;
         let bit_pos = idx % self.block_size;
         self.blocks[block_pos] as usize + self.bit_vec[idx - bit_pos..idx].count_ones()
     }
 
+    /// Answers `query` like every [`RankSelect`] does, so callers do not need the trait in scope.
+    pub fn process(&self, query: &Query) -> QueryResult {
+        RankSelect::process(self, query)
+    }
+
     pub fn select0(&self, mut rank: usize) -> Option<usize> {
         assert!(rank > 0);
         assert!(rank <= self.bit_vec.len());
@@ -126,21 +132,52 @@ impl<'a> NaiveVector<'a> {
         }
     }
 
-    pub fn process(&self, query: &Query) -> QueryResult {
-        match query {
-            Access(idx) => QueryResult::Access(self.bit_vec[*idx]),
-            Rank(w, idx) => QueryResult::Rank(match *w {
-                true => self.rank1(*idx),
-                false => self.rank0(*idx),
-            }),
-            Select(w, nth) => QueryResult::Select(match *w {
-                true => self.select1(*nth),
-                false => self.select0(*nth),
-            }),
+    pub fn space_usage(&self) -> usize {
+        (self.blocks.len() * 64) + mem::size_of::<Self>()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            slack: 0,
+            ..space_report::bit_vec(self.bit_vec)
+        } + SpaceReport {
+            l0: space_report::bits(&self.blocks),
+            slack: space_report::slack(&self.blocks),
+            ..SpaceReport::of::<Self>()
         }
     }
+}
 
-    pub fn space_usage(&self) -> usize {
-        (self.blocks.len() * 64) + mem::size_of::<Self>()
+impl RankSelect for NaiveVector<'_> {
+    fn len(&self) -> usize {
+        self.bit_vec.len()
+    }
+
+    fn access(&self, idx: usize) -> bool {
+        self.bit_vec[idx]
+    }
+
+    fn rank0(&self, idx: usize) -> usize {
+        self.rank0(idx)
+    }
+
+    fn rank1(&self, idx: usize) -> usize {
+        self.rank1(idx)
+    }
+
+    fn select0(&self, rank: usize) -> Option<usize> {
+        self.select0(rank)
+    }
+
+    fn select1(&self, rank: usize) -> Option<usize> {
+        self.select1(rank)
+    }
+
+    fn space_usage(&self) -> usize {
+        self.space_usage()
+    }
+
+    fn space_report(&self) -> SpaceReport {
+        self.space_report()
     }
 }
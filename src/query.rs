@@ -5,6 +5,12 @@ pub enum Query {
     Access(usize),
     Rank(bool, usize),
     Select(bool, usize),
+    Next(bool, usize),
+    Prev(bool, usize),
+    /// Counts the ones or zeros in `start..end`.
+    RangeCount(bool, usize, usize),
+    /// Finds the `nth` one or zero in `start..end`.
+    RangeSelect(bool, usize, usize, usize),
 }
 
 impl TryFrom<&str> for Query {
@@ -24,25 +30,55 @@ impl TryFrom<&str> for Query {
             let which_bit = line[7..8].parse::<usize>().map_err(|_| ())? == 1;
             let nth = line[9..].trim().parse::<usize>().map_err(|_| ())?;
             Ok(Select(which_bit, nth))
+        } else if (line[0..5] == *"next " || line[0..5] == *"prev ") && line[6..7] == *" " {
+            let which_bit = line[5..6].parse::<usize>().map_err(|_| ())? == 1;
+            let idx = line[7..].trim().parse::<usize>().map_err(|_| ())?;
+            match &line[0..4] {
+                "next" => Ok(Next(which_bit, idx)),
+                _ => Ok(Prev(which_bit, idx)),
+            }
+        } else if let Some(args) = line.strip_prefix("count ") {
+            match parse_args(args)?[..] {
+                [bit, start, end] if bit <= 1 && start <= end => {
+                    Ok(RangeCount(bit == 1, start, end))
+                }
+                _ => Err(()),
+            }
+        } else if let Some(args) = line.strip_prefix("select_in ") {
+            match parse_args(args)?[..] {
+                [bit, start, end, nth] if bit <= 1 && start <= end => {
+                    Ok(RangeSelect(bit == 1, start, end, nth))
+                }
+                _ => Err(()),
+            }
         } else {
             Err(())
         }
     }
 }
 
+// Parses the whitespace separated numbers of a query with a range.
+fn parse_args(args: &str) -> Result<Vec<usize>, ()> {
+    args.split_whitespace()
+        .map(|arg| arg.parse::<usize>().map_err(|_| ()))
+        .collect()
+}
+
 #[derive(Debug)]
 pub enum QueryResult {
     Access(bool),
     Rank(usize),
     Select(Option<usize>),
+    Count(usize),
+    Position(Option<usize>),
 }
 
 impl QueryResult {
     pub fn as_line(&self) -> String {
         match self {
             QueryResult::Access(b) => format!("{}\n", *b as u8),
-            QueryResult::Rank(r) => format!("{}\n", r),
-            QueryResult::Select(opt) => {
+            QueryResult::Rank(r) | QueryResult::Count(r) => format!("{}\n", r),
+            QueryResult::Select(opt) | QueryResult::Position(opt) => {
                 opt.map_or_else(|| "None\n".to_string(), |s| format!("{}\n", s))
             }
         }
@@ -81,6 +117,33 @@ mod tests {
         assert_eq!(Query::try_from(select2), Ok(Select(true, 1)));
     }
 
+    #[test]
+    fn next_and_prev() {
+        assert_eq!(Query::try_from("next 1 17\n"), Ok(Next(true, 17)));
+        assert_eq!(Query::try_from("prev 0 0"), Ok(Prev(false, 0)));
+        assert_eq!(Query::try_from("next 1 x\n"), Err(()));
+        assert_eq!(QueryResult::Position(None).as_line(), "None\n");
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            Query::try_from("count 1 5 20\n"),
+            Ok(RangeCount(true, 5, 20))
+        );
+        assert_eq!(Query::try_from("count 0 0 0"), Ok(RangeCount(false, 0, 0)));
+        assert_eq!(
+            Query::try_from("select_in 1 100 200 3\n"),
+            Ok(RangeSelect(true, 100, 200, 3))
+        );
+        assert_eq!(Query::try_from("count 1 5\n"), Err(()));
+        assert_eq!(Query::try_from("count 2 5 6\n"), Err(()));
+        assert_eq!(Query::try_from("select_in 0 1 2 3 4"), Err(()));
+        assert_eq!(Query::try_from("count 1 20 5\n"), Err(()));
+        assert_eq!(Query::try_from("select_in 1 20 5 1"), Err(()));
+        assert_eq!(QueryResult::Count(7).as_line(), "7\n");
+    }
+
     #[test]
     fn bad() {
         let bad1 = "   \n \n ";
@@ -0,0 +1,529 @@
+//! # Rank9
+//!
+//! The rank and select structures of Sebastiano Vigna's
+//! [Broadword Implementation of Rank/Select Queries](https://doi.org/10.1007/978-3-540-68552-4_12),
+//! to compare them with [`crate::runaway_vector::RunawayVector`] on the same inputs.
+//!
+//! Both vectors answer rank with the rank9 counters: for every block of eight words the ones in
+//! front of the block, followed by a word holding the ones in front of each of the words 1 to 7
+//! inside the block in 9 bits each. They differ in select:
+//!
+//! * [`Rank9`] uses select9, an inventory with the block of every [`SELECT9_SAMPLE_RATE`]-th one,
+//!   which bounds a binary search over the blocks. The word inside the block is found with a
+//!   broadword comparison of all seven counts at once.
+//! * [`SimpleSelect`] uses simple-select, an inventory with the position of every
+//!   [`SIMPLE_SAMPLE_RATE`]-th one, from which the vector is scanned a word at a time. The spill
+//!   for far apart ones of the original is left out, so sparse regions are scanned in full.
+//!
+//! Both keep a second inventory for the zeros.
+//!
+use std::mem;
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::rank_select::RankSelect;
+use crate::space_report::{self, SpaceReport};
+use crate::word::{self, select_in_word};
+
+pub const SELECT9_SAMPLE_RATE: usize = 512;
+pub const SIMPLE_SAMPLE_RATE: usize = 1024;
+
+const BLOCK_WORDS: usize = 8;
+const BLOCK_BIT_SIZE: usize = BLOCK_WORDS * 64;
+
+// A one at the lowest bit of each of the seven 9 bit counts of a block.
+const ONES_STEP_9: u64 = 1 | 1 << 9 | 1 << 18 | 1 << 27 | 1 << 36 | 1 << 45 | 1 << 54;
+const MSBS_STEP_9: u64 = 0x100 * ONES_STEP_9;
+// The number of bits in front of each of the words 1 to 7 of a block.
+const WORD_OFFSETS: u64 = 64 | 128 << 9 | 192 << 18 | 256 << 27 | 320 << 36 | 384 << 45 | 448 << 54;
+
+// The `i`-th word of `bit_vec`, inverted for zeros, without the unused bits behind its end.
+fn word(bit_vec: &BitVec<u64, Lsb0>, i: usize, bit: bool) -> u64 {
+    let word = bit_vec.as_raw_slice()[i];
+    let word = if bit { word } else { !word };
+    let end = bit_vec.len() - 64 * i;
+    if end < 64 {
+        word & ((1 << end) - 1)
+    } else {
+        word
+    }
+}
+
+// Compares each 9 bit count in `x` with the one in `y` and sets the highest bit of the count if
+// it is less or equal.
+fn uleq_step_9(x: u64, y: u64) -> u64 {
+    ((((y | MSBS_STEP_9) - (x & !MSBS_STEP_9)) | (x ^ y)) ^ (x & !y)) & MSBS_STEP_9
+}
+
+// The rank9 counters, which are shared by both vectors.
+struct Counts {
+    // Two words per block: the ones in front of the block and the seven counts inside it.
+    counts: Vec<u64>,
+    ones: usize,
+}
+
+impl Counts {
+    fn new(bit_vec: &BitVec<u64, Lsb0>) -> Self {
+        // The counters and the queries read the raw words.
+        assert!(
+            word::is_aligned(bit_vec),
+            "bits do not start at the first bit of a word"
+        );
+        let words = bit_vec.as_raw_slice().len();
+        let blocks = words.div_ceil(BLOCK_WORDS);
+        let mut counts = Vec::with_capacity(2 * (blocks + 1));
+        let mut ones = 0;
+        for block in 0..blocks {
+            counts.push(ones as u64);
+            let mut relative = 0;
+            let mut in_block = 0;
+            for k in 0..BLOCK_WORDS {
+                if k > 0 {
+                    relative |= in_block << (9 * (k - 1));
+                }
+                let i = block * BLOCK_WORDS + k;
+                if i < words {
+                    in_block += word(bit_vec, i, true).count_ones() as u64;
+                }
+            }
+            counts.push(relative);
+            ones += in_block as usize;
+        }
+        // The block behind the last one ends the search over the blocks.
+        counts.push(ones as u64);
+        counts.push(0);
+
+        Counts { counts, ones }
+    }
+
+    fn blocks(&self) -> usize {
+        self.counts.len() / 2 - 1
+    }
+
+    // The ones or zeros in front of `block`.
+    fn absolute(&self, block: usize, bit: bool) -> usize {
+        let ones = self.counts[2 * block] as usize;
+        if bit {
+            ones
+        } else {
+            block * BLOCK_BIT_SIZE - ones
+        }
+    }
+
+    // The seven counts of ones or zeros inside `block`.
+    fn relative(&self, block: usize, bit: bool) -> u64 {
+        let ones = self.counts[2 * block + 1];
+        if bit {
+            ones
+        } else {
+            WORD_OFFSETS - ones
+        }
+    }
+
+    fn total(&self, len: usize, bit: bool) -> usize {
+        if bit {
+            self.ones
+        } else {
+            len - self.ones
+        }
+    }
+
+    fn rank1(&self, bit_vec: &BitVec<u64, Lsb0>, idx: usize) -> usize {
+        assert!(idx <= bit_vec.len());
+        if idx == bit_vec.len() {
+            return self.ones;
+        }
+        let block = idx / BLOCK_BIT_SIZE;
+        let k = (idx / 64) % BLOCK_WORDS;
+        let mut rank = self.absolute(block, true);
+        if k > 0 {
+            rank += (self.relative(block, true) >> (9 * (k - 1)) & 0x1FF) as usize;
+        }
+        let mask = (1 << (idx % 64)) - 1;
+        rank + (word(bit_vec, idx / 64, true) & mask).count_ones() as usize
+    }
+
+    // Finds the `rank`-th one or zero inside `block`, starting with 0.
+    fn select_in_block(
+        &self,
+        bit_vec: &BitVec<u64, Lsb0>,
+        block: usize,
+        rank: usize,
+        bit: bool,
+    ) -> usize {
+        let relative = self.relative(block, bit);
+        let k = uleq_step_9(relative, rank as u64 * ONES_STEP_9).count_ones() as usize;
+        let before = match k {
+            0 => 0,
+            _ => (relative >> (9 * (k - 1)) & 0x1FF) as usize,
+        };
+        let i = block * BLOCK_WORDS + k;
+        64 * i + select_in_word(word(bit_vec, i, bit), rank - before + 1)
+    }
+
+    fn space_usage(&self) -> usize {
+        self.counts.len() * 64
+    }
+
+    // The words with the ones in front of the blocks are the L0 counters and the words with the
+    // counts inside the blocks the L1/L2 counters.
+    fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            l0: self.counts.len() / 2 * 64,
+            l12: self.counts.len() / 2 * 64,
+            slack: space_report::slack(&self.counts),
+            ..SpaceReport::default()
+        }
+    }
+}
+
+pub struct Rank9<'a> {
+    bit_vec: &'a BitVec<u64, Lsb0>,
+    counts: Counts,
+    // The block of every `SELECT9_SAMPLE_RATE`-th zero and one.
+    inventories: [Vec<u64>; 2],
+}
+
+impl<'a> Rank9<'a> {
+    /// # Panics
+    ///
+    /// If the bits do not start at the lowest bit of their first word, like a slice of another
+    /// vector.
+    pub fn new(bit_vec: &'a BitVec<u64, Lsb0>) -> Self {
+        let counts = Counts::new(bit_vec);
+        let mut inventories = [Vec::new(), Vec::new()];
+        for bit in [false, true] {
+            let inventory = &mut inventories[bit as usize];
+            let mut before = 0;
+            for i in 0..bit_vec.as_raw_slice().len() {
+                let count = word(bit_vec, i, bit).count_ones() as usize;
+                while inventory.len() * SELECT9_SAMPLE_RATE < before + count {
+                    inventory.push((i / BLOCK_WORDS) as u64);
+                }
+                before += count;
+            }
+        }
+
+        Rank9 {
+            bit_vec,
+            counts,
+            inventories,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bit_vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bit_vec.is_empty()
+    }
+
+    pub fn access(&self, idx: usize) -> bool {
+        self.bit_vec[idx]
+    }
+
+    pub fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank1(idx)
+    }
+
+    /// Counts the ones in front of `idx`, which may be the length of the vector.
+    pub fn rank1(&self, idx: usize) -> usize {
+        self.counts.rank1(self.bit_vec, idx)
+    }
+
+    pub fn select0(&self, rank: usize) -> Option<usize> {
+        self.select(rank, false)
+    }
+
+    pub fn select1(&self, rank: usize) -> Option<usize> {
+        self.select(rank, true)
+    }
+
+    fn select(&self, rank: usize, bit: bool) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.counts.total(self.len(), bit) {
+            return None;
+        }
+        let rank = rank - 1;
+        let inventory = &self.inventories[bit as usize];
+        let sample = rank / SELECT9_SAMPLE_RATE;
+        // The block of the sampled one is the first and the block of the next sample the last
+        // block which can hold the queried one.
+        let mut l = inventory[sample] as usize;
+        let mut r = inventory
+            .get(sample + 1)
+            .map_or(self.counts.blocks() - 1, |block| *block as usize);
+        while l < r {
+            let m = (l + r).div_ceil(2);
+            if self.counts.absolute(m, bit) <= rank {
+                l = m;
+            } else {
+                r = m - 1;
+            }
+        }
+        let rank = rank - self.counts.absolute(l, bit);
+        Some(self.counts.select_in_block(self.bit_vec, l, rank, bit))
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.counts.space_usage()
+            + self.inventories.iter().map(Vec::len).sum::<usize>() * 64
+            + mem::size_of::<Self>()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        let inventories = SpaceReport {
+            select_samples: self
+                .inventories
+                .iter()
+                .map(|inv| space_report::bits(inv))
+                .sum(),
+            slack: self.inventories.iter().map(space_report::slack).sum(),
+            ..SpaceReport::of::<Self>()
+        };
+        let bits = SpaceReport {
+            slack: 0,
+            ..space_report::bit_vec(self.bit_vec)
+        };
+        bits + self.counts.space_report() + inventories
+    }
+}
+
+pub struct SimpleSelect<'a> {
+    bit_vec: &'a BitVec<u64, Lsb0>,
+    counts: Counts,
+    // The position of every `SIMPLE_SAMPLE_RATE`-th zero and one.
+    inventories: [Vec<u64>; 2],
+}
+
+impl<'a> SimpleSelect<'a> {
+    /// # Panics
+    ///
+    /// If the bits do not start at the lowest bit of their first word, like a slice of another
+    /// vector.
+    pub fn new(bit_vec: &'a BitVec<u64, Lsb0>) -> Self {
+        let counts = Counts::new(bit_vec);
+        let mut inventories = [Vec::new(), Vec::new()];
+        for bit in [false, true] {
+            let inventory = &mut inventories[bit as usize];
+            let mut before = 0;
+            for i in 0..bit_vec.as_raw_slice().len() {
+                let word = word(bit_vec, i, bit);
+                let count = word.count_ones() as usize;
+                while inventory.len() * SIMPLE_SAMPLE_RATE < before + count {
+                    let rank = inventory.len() * SIMPLE_SAMPLE_RATE - before + 1;
+                    inventory.push((64 * i + select_in_word(word, rank)) as u64);
+                }
+                before += count;
+            }
+        }
+
+        SimpleSelect {
+            bit_vec,
+            counts,
+            inventories,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bit_vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bit_vec.is_empty()
+    }
+
+    pub fn access(&self, idx: usize) -> bool {
+        self.bit_vec[idx]
+    }
+
+    pub fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank1(idx)
+    }
+
+    /// Counts the ones in front of `idx`, which may be the length of the vector.
+    pub fn rank1(&self, idx: usize) -> usize {
+        self.counts.rank1(self.bit_vec, idx)
+    }
+
+    pub fn select0(&self, rank: usize) -> Option<usize> {
+        self.select(rank, false)
+    }
+
+    pub fn select1(&self, rank: usize) -> Option<usize> {
+        self.select(rank, true)
+    }
+
+    fn select(&self, rank: usize, bit: bool) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.counts.total(self.len(), bit) {
+            return None;
+        }
+        let rank = rank - 1;
+        let position = self.inventories[bit as usize][rank / SIMPLE_SAMPLE_RATE] as usize;
+        // The ones which are left to skip, starting at the sampled one.
+        let mut skip = rank % SIMPLE_SAMPLE_RATE;
+        let mut i = position / 64;
+        let mut word = word(self.bit_vec, i, bit) & (u64::MAX << (position % 64));
+        loop {
+            let count = word.count_ones() as usize;
+            if skip < count {
+                return Some(64 * i + select_in_word(word, skip + 1));
+            }
+            skip -= count;
+            i += 1;
+            word = self::word(self.bit_vec, i, bit);
+        }
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.counts.space_usage()
+            + self.inventories.iter().map(Vec::len).sum::<usize>() * 64
+            + mem::size_of::<Self>()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        let inventories = SpaceReport {
+            select_samples: self
+                .inventories
+                .iter()
+                .map(|inv| space_report::bits(inv))
+                .sum(),
+            slack: self.inventories.iter().map(space_report::slack).sum(),
+            ..SpaceReport::of::<Self>()
+        };
+        let bits = SpaceReport {
+            slack: 0,
+            ..space_report::bit_vec(self.bit_vec)
+        };
+        bits + self.counts.space_report() + inventories
+    }
+}
+
+impl RankSelect for Rank9<'_> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn access(&self, idx: usize) -> bool {
+        self.access(idx)
+    }
+
+    fn rank0(&self, idx: usize) -> usize {
+        self.rank0(idx)
+    }
+
+    fn rank1(&self, idx: usize) -> usize {
+        self.rank1(idx)
+    }
+
+    fn select0(&self, rank: usize) -> Option<usize> {
+        self.select0(rank)
+    }
+
+    fn select1(&self, rank: usize) -> Option<usize> {
+        self.select1(rank)
+    }
+
+    fn space_usage(&self) -> usize {
+        self.space_usage()
+    }
+
+    fn space_report(&self) -> SpaceReport {
+        self.space_report()
+    }
+}
+
+impl RankSelect for SimpleSelect<'_> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn access(&self, idx: usize) -> bool {
+        self.access(idx)
+    }
+
+    fn rank0(&self, idx: usize) -> usize {
+        self.rank0(idx)
+    }
+
+    fn rank1(&self, idx: usize) -> usize {
+        self.rank1(idx)
+    }
+
+    fn select0(&self, rank: usize) -> Option<usize> {
+        self.select0(rank)
+    }
+
+    fn select1(&self, rank: usize) -> Option<usize> {
+        self.select1(rank)
+    }
+
+    fn space_usage(&self) -> usize {
+        self.space_usage()
+    }
+
+    fn space_report(&self) -> SpaceReport {
+        self.space_report()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rank_select::tests::{random_bit_vec, same_as_runaway};
+
+    fn check(bit_vec: &BitVec<u64, Lsb0>) {
+        same_as_runaway(&Rank9::new(bit_vec), bit_vec);
+        same_as_runaway(&SimpleSelect::new(bit_vec), bit_vec);
+    }
+
+    #[test]
+    fn broadword_comparison() {
+        let counts = (1..8).fold(0, |counts, k| counts | (k * 60) << (9 * (k - 1)));
+        for rank in 0..512 {
+            let expected = (1..8).filter(|k| k * 60 <= rank).count() as u32;
+            let leq = uleq_step_9(counts, rank * ONES_STEP_9);
+            assert_eq!(leq.count_ones(), expected);
+        }
+    }
+
+    #[test]
+    fn random() {
+        for (len, density) in [
+            (1, 0.5),
+            (100, 0.5),
+            (5000, 0.5),
+            (20_000, 0.05),
+            (20_000, 0.97),
+        ] {
+            check(&random_bit_vec(len, density, len as u64));
+        }
+    }
+
+    #[test]
+    fn edges() {
+        check(&BitVec::repeat(true, 4096));
+        check(&BitVec::repeat(false, 4100));
+        // The unused bits behind the end must not be counted.
+        let mut bit_vec: BitVec<u64, Lsb0> = BitVec::repeat(true, 1100);
+        bit_vec.truncate(1030);
+        check(&bit_vec);
+    }
+
+    #[test]
+    #[should_panic(expected = "bits do not start at the first bit of a word")]
+    fn unaligned_rank9() {
+        let bit_vec: BitVec<u64, Lsb0> = BitVec::repeat(true, 1000);
+        Rank9::new(&bit_vec[5..].to_bitvec());
+    }
+
+    #[test]
+    #[should_panic(expected = "bits do not start at the first bit of a word")]
+    fn unaligned_simple_select() {
+        let bit_vec: BitVec<u64, Lsb0> = BitVec::repeat(true, 1000);
+        SimpleSelect::new(&bit_vec[5..].to_bitvec());
+    }
+}
@@ -0,0 +1,189 @@
+//! # RankSelect
+//!
+//! The operations which every bit vector of this crate supports, so that they can be swapped for
+//! each other and answer the queries of [`crate::query`] in the same way.
+//!
+use std::ops::Range;
+
+use crate::query::Query::{Access, Next, Prev, RangeCount, RangeSelect, Rank, Select};
+use crate::query::{Query, QueryResult};
+use crate::space_report::SpaceReport;
+
+pub trait RankSelect {
+    /// The number of bits in the vector.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn access(&self, idx: usize) -> bool;
+
+    /// Counts the zeros in front of `idx`.
+    fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank1(idx)
+    }
+
+    /// Counts the ones in front of `idx`.
+    fn rank1(&self, idx: usize) -> usize;
+
+    /// Finds the position of the `rank`-th zero, starting with 1.
+    fn select0(&self, rank: usize) -> Option<usize>;
+
+    /// Finds the position of the `rank`-th one, starting with 1.
+    fn select1(&self, rank: usize) -> Option<usize>;
+
+    /// Counts the ones in `range`.
+    fn count_ones(&self, range: Range<usize>) -> usize {
+        assert!(range.start <= range.end);
+        self.rank1(range.end) - self.rank1(range.start)
+    }
+
+    /// Counts the zeros in `range`.
+    fn count_zeros(&self, range: Range<usize>) -> usize {
+        range.len() - self.count_ones(range)
+    }
+
+    /// Finds the position of the `rank`-th one in `range`, starting with 1.
+    fn select1_in(&self, range: Range<usize>, rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.count_ones(range.clone()) {
+            return None;
+        }
+        self.select1(self.rank1(range.start) + rank)
+    }
+
+    /// Finds the position of the `rank`-th zero in `range`, starting with 1.
+    fn select0_in(&self, range: Range<usize>, rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.count_zeros(range.clone()) {
+            return None;
+        }
+        self.select0(self.rank0(range.start) + rank)
+    }
+
+    /// Finds the first one at or after `idx`.
+    fn next_one(&self, idx: usize) -> Option<usize> {
+        assert!(idx < self.len());
+        let rank = self.rank1(idx);
+        if rank == self.rank1(self.len()) {
+            None
+        } else {
+            self.select1(rank + 1)
+        }
+    }
+
+    /// Finds the last one at or before `idx`.
+    fn prev_one(&self, idx: usize) -> Option<usize> {
+        assert!(idx < self.len());
+        match self.rank1(idx + 1) {
+            0 => None,
+            rank => self.select1(rank),
+        }
+    }
+
+    /// Finds the first zero at or after `idx`.
+    fn next_zero(&self, idx: usize) -> Option<usize> {
+        assert!(idx < self.len());
+        let rank = self.rank0(idx);
+        if rank == self.rank0(self.len()) {
+            None
+        } else {
+            self.select0(rank + 1)
+        }
+    }
+
+    /// Finds the last zero at or before `idx`.
+    fn prev_zero(&self, idx: usize) -> Option<usize> {
+        assert!(idx < self.len());
+        match self.rank0(idx + 1) {
+            0 => None,
+            rank => self.select0(rank),
+        }
+    }
+
+    /// The space used in bits. Vectors which only index a plain bit vector do not count the bits
+    /// themselves, while compressed vectors count their whole representation.
+    fn space_usage(&self) -> usize;
+
+    /// The space split into its parts, including the bits of vectors which only index them.
+    fn space_report(&self) -> SpaceReport;
+
+    fn process(&self, query: &Query) -> QueryResult {
+        match query {
+            Access(idx) => QueryResult::Access(self.access(*idx)),
+            Rank(w, idx) => QueryResult::Rank(match *w {
+                true => self.rank1(*idx),
+                false => self.rank0(*idx),
+            }),
+            Select(w, nth) => QueryResult::Select(match *w {
+                true => self.select1(*nth),
+                false => self.select0(*nth),
+            }),
+            Next(w, idx) => QueryResult::Position(match *w {
+                true => self.next_one(*idx),
+                false => self.next_zero(*idx),
+            }),
+            Prev(w, idx) => QueryResult::Position(match *w {
+                true => self.prev_one(*idx),
+                false => self.prev_zero(*idx),
+            }),
+            RangeCount(w, start, end) => QueryResult::Count(match *w {
+                true => self.count_ones(*start..*end),
+                false => self.count_zeros(*start..*end),
+            }),
+            RangeSelect(w, start, end, nth) => QueryResult::Select(match *w {
+                true => self.select1_in(*start..*end, *nth),
+                false => self.select0_in(*start..*end, *nth),
+            }),
+        }
+    }
+}
+
+/// Helpers for the tests of the vectors, which check them against a
+/// [`crate::runaway_vector::RunawayVector`] over the same bits.
+#[cfg(test)]
+pub(crate) mod tests {
+    use bitvec::order::Lsb0;
+    use bitvec::prelude::BitVec;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::RankSelect;
+    use crate::runaway_vector::RunawayVector;
+
+    /// `len` random bits, each of which is set with a probability of `density`.
+    pub(crate) fn random_bit_vec(len: usize, density: f64, seed: u64) -> BitVec<u64, Lsb0> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        (0..len).map(|_| rng.gen_bool(density)).collect()
+    }
+
+    /// Checks every access, rank and select of `vector`, which was built over `bit_vec`, and the
+    /// next and previous bits at every fifth position.
+    pub(crate) fn same_as_runaway(vector: &dyn RankSelect, bit_vec: &BitVec<u64, Lsb0>) {
+        let expected = RunawayVector::new(bit_vec);
+        let len = bit_vec.len();
+        assert_eq!(vector.len(), len);
+        for i in 0..len {
+            assert_eq!(vector.access(i), bit_vec[i]);
+        }
+        for i in 0..=len {
+            assert_eq!(vector.rank1(i), expected.rank1(i));
+        }
+        let ones = expected.rank1(len);
+        for rank in 1..=ones {
+            assert_eq!(vector.select1(rank), expected.select1(rank));
+        }
+        for rank in 1..=len - ones {
+            assert_eq!(vector.select0(rank), expected.select0(rank));
+        }
+        for i in (0..len).step_by(5) {
+            assert_eq!(vector.next_one(i), expected.next_one(i));
+            assert_eq!(vector.prev_one(i), expected.prev_one(i));
+            assert_eq!(vector.next_zero(i), expected.next_zero(i));
+            assert_eq!(vector.prev_zero(i), expected.prev_zero(i));
+        }
+        assert_eq!(vector.select1(ones + 1), None);
+        assert_eq!(vector.select0(len - ones + 1), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! # RleVector
+//!
+//! A bit vector which is stored as its runs of ones, as presented by
+//! [Mäkinen and Navarro](https://doi.org/10.1007/11496656_5). Two [`SparseVector`]s hold the
+//! position at which each run of ones starts and the number of ones in front of each run. All
+//! operations find a run with a rank or select on one of them, so they take `O(log r)` for `r`
+//! runs and the space only depends on the number of runs, not on the length of the vector.
+//!
+use std::mem;
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::rank_select::RankSelect;
+use crate::space_report::SpaceReport;
+use crate::sparse_vector::SparseVector;
+
+pub struct RleVector {
+    len: usize,
+    ones: usize,
+    // The position of the first bit of each run of ones.
+    starts: SparseVector,
+    // The number of ones in front of each run of ones.
+    offsets: SparseVector,
+}
+
+impl RleVector {
+    pub fn new(bit_vec: &BitVec<u64, Lsb0>) -> Self {
+        let mut starts = Vec::new();
+        let mut offsets = Vec::new();
+        let mut ones = 0;
+        for pos in bit_vec.iter_ones() {
+            if pos == 0 || !bit_vec[pos - 1] {
+                starts.push(pos);
+                offsets.push(ones);
+            }
+            ones += 1;
+        }
+        RleVector {
+            len: bit_vec.len(),
+            ones,
+            starts: SparseVector::from_positions(bit_vec.len(), &starts),
+            offsets: SparseVector::from_positions(ones, &offsets),
+        }
+    }
+
+    pub fn to_bit_vec(&self) -> BitVec<u64, Lsb0> {
+        let mut bit_vec = BitVec::repeat(false, self.len);
+        for run in 0..self.runs() {
+            let (start, len) = self.run(run);
+            bit_vec[start..start + len].fill(true);
+        }
+        bit_vec
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of runs of ones.
+    pub fn runs(&self) -> usize {
+        self.offsets.ones()
+    }
+
+    pub fn access(&self, idx: usize) -> bool {
+        assert!(idx < self.len);
+        match self.starts.rank1(idx + 1) {
+            0 => false,
+            runs => {
+                let (start, len) = self.run(runs - 1);
+                idx < start + len
+            }
+        }
+    }
+
+    pub fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank1(idx)
+    }
+
+    /// Counts the ones in front of `idx`. `idx` may be the length of the vector.
+    pub fn rank1(&self, idx: usize) -> usize {
+        assert!(idx <= self.len);
+        match self.starts.rank1(idx) {
+            0 => 0,
+            runs => {
+                let (start, len) = self.run(runs - 1);
+                self.offset(runs - 1) + len.min(idx - start)
+            }
+        }
+    }
+
+    pub fn select0(&self, rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.len - self.ones {
+            return None;
+        }
+        // Binary search for the number of runs which have less than `rank` zeros in front of them.
+        let mut l = 0;
+        let mut r = self.runs();
+        while l < r {
+            let m = (l + r) / 2;
+            if self.starts.select1(m + 1).unwrap() - self.offset(m) < rank {
+                l = m + 1;
+            } else {
+                r = m;
+            }
+        }
+        Some(rank - 1 + self.offset(l))
+    }
+
+    pub fn select1(&self, rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.ones {
+            return None;
+        }
+        let run = self.offsets.rank1(rank) - 1;
+        Some(self.starts.select1(run + 1).unwrap() + (rank - 1 - self.offset(run)))
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.starts.space_usage() + self.offsets.space_usage() + mem::size_of::<Self>()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport::of::<Self>()
+            + self.starts.space_report().inline()
+            + self.offsets.space_report().inline()
+    }
+
+    // The number of ones in front of the run, which is the number of all ones behind the last run.
+    fn offset(&self, run: usize) -> usize {
+        if run == self.runs() {
+            self.ones
+        } else {
+            self.offsets.select1(run + 1).unwrap()
+        }
+    }
+
+    // The start and the length of the run.
+    fn run(&self, run: usize) -> (usize, usize) {
+        let start = self.starts.select1(run + 1).unwrap();
+        (start, self.offset(run + 1) - self.offset(run))
+    }
+}
+
+impl From<&BitVec<u64, Lsb0>> for RleVector {
+    fn from(bit_vec: &BitVec<u64, Lsb0>) -> Self {
+        Self::new(bit_vec)
+    }
+}
+
+impl From<&RleVector> for BitVec<u64, Lsb0> {
+    fn from(rle_vector: &RleVector) -> Self {
+        rle_vector.to_bit_vec()
+    }
+}
+
+impl RankSelect for RleVector {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn access(&self, idx: usize) -> bool {
+        self.access(idx)
+    }
+
+    fn rank0(&self, idx: usize) -> usize {
+        self.rank0(idx)
+    }
+
+    fn rank1(&self, idx: usize) -> usize {
+        self.rank1(idx)
+    }
+
+    fn select0(&self, rank: usize) -> Option<usize> {
+        self.select0(rank)
+    }
+
+    fn select1(&self, rank: usize) -> Option<usize> {
+        self.select1(rank)
+    }
+
+    fn space_usage(&self) -> usize {
+        self.space_usage()
+    }
+
+    fn space_report(&self) -> SpaceReport {
+        self.space_report()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rank_select::tests;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_runs(len: usize, mean_run: usize, seed: u64) -> BitVec<u64, Lsb0> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut bit_vec = BitVec::with_capacity(len);
+        let mut bit = rng.gen_bool(0.5);
+        while bit_vec.len() < len {
+            let run = rng.gen_range(1..2 * mean_run).min(len - bit_vec.len());
+            bit_vec.extend((0..run).map(|_| bit));
+            bit = !bit;
+        }
+        bit_vec
+    }
+
+    #[test]
+    fn same_as_runaway() {
+        for (len, mean_run) in [(30_000, 500), (5000, 3), (2000, 1), (1000, 2000)] {
+            let bv = random_runs(len, mean_run, len as u64);
+            tests::same_as_runaway(&RleVector::new(&bv), &bv);
+        }
+    }
+
+    #[test]
+    fn conversion() {
+        let bv = random_runs(100_000, 1000, 7);
+        let rle = RleVector::from(&bv);
+        assert_eq!(BitVec::from(&rle), bv);
+        assert!(rle.space_usage() < bv.len() / 10);
+        let empty = RleVector::new(&BitVec::new());
+        assert!(empty.is_empty());
+        assert_eq!(empty.runs(), 0);
+        assert_eq!(empty.to_bit_vec(), BitVec::<u64, Lsb0>::new());
+    }
+}
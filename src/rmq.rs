@@ -0,0 +1,129 @@
+//! # Rmq
+//!
+//! Range minimum queries in `2n + o(n)` bits with the 2d-min-heap of
+//! [Fischer and Heun](https://doi.org/10.1137/090779759), stored as balanced parentheses as
+//! presented by [Ferrada and Navarro](https://doi.org/10.1016/j.jda.2017.01.002). The values are
+//! only needed while building, queries are answered from the parentheses alone.
+//!
+//! The values are pushed onto a stack from left to right. Before a value is pushed, every larger
+//! value is popped from the stack and written as a closing parenthesis, then the value itself is
+//! written as an opening parenthesis. A leading opening parenthesis stands for a root below all
+//! values. The leftmost minimum of a range stays on the stack until the end of the range, so it is
+//! the last value in the range whose opening parenthesis has the minimal excess.
+//!
+use std::ops::Range;
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::balanced_parentheses::BalancedParentheses;
+use crate::space_report::SpaceReport;
+
+pub struct Rmq {
+    parentheses: BalancedParentheses,
+}
+
+impl Rmq {
+    pub fn new<T: Ord>(values: &[T]) -> Self {
+        let mut bit_vec: BitVec<u64, Lsb0> = BitVec::with_capacity(2 * values.len() + 2);
+        bit_vec.push(true);
+        let mut stack: Vec<&T> = Vec::new();
+        for value in values {
+            while stack.last().is_some_and(|top| *top > value) {
+                stack.pop();
+                bit_vec.push(false);
+            }
+            stack.push(value);
+            bit_vec.push(true);
+        }
+        bit_vec.extend((0..=stack.len()).map(|_| false));
+        Rmq {
+            parentheses: BalancedParentheses::new(bit_vec),
+        }
+    }
+
+    /// The number of values.
+    pub fn len(&self) -> usize {
+        self.parentheses.len() / 2 - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Finds the position of the leftmost minimum in `range`.
+    pub fn argmin(&self, range: Range<usize>) -> usize {
+        assert!(
+            range.start < range.end && range.end <= self.len(),
+            "invalid range"
+        );
+        let start = self.open(range.start);
+        let end = self.open(range.end - 1);
+        let min = self
+            .parentheses
+            .excess(self.parentheses.range_min(start, end));
+        // The minimum stays on the stack until the end of the range, so every later parenthesis
+        // has a larger excess and the last position with the minimal excess is its opening one.
+        let diff = min - self.parentheses.excess(end + 1);
+        let open = self.parentheses.bwd_search(end + 1, diff).unwrap();
+        self.parentheses.bits().rank1(open) - 1
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.parentheses.space_usage()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        self.parentheses.space_report()
+    }
+
+    // The opening parenthesis of the value at `idx`, behind the one of the root.
+    fn open(&self, idx: usize) -> usize {
+        self.parentheses.bits().select1(idx + 2).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn naive_argmin(values: &[u32], range: Range<usize>) -> usize {
+        let min = values[range.clone()].iter().min().unwrap();
+        range.start + values[range].iter().position(|value| value == min).unwrap()
+    }
+
+    #[test]
+    fn small() {
+        let values = [3, 1, 4, 1, 5, 9, 2, 6];
+        let rmq = Rmq::new(&values);
+        assert_eq!(rmq.len(), values.len());
+        assert_eq!(rmq.argmin(0..8), 1);
+        assert_eq!(rmq.argmin(2..8), 3);
+        assert_eq!(rmq.argmin(4..8), 6);
+        assert_eq!(rmq.argmin(4..6), 4);
+        assert_eq!(rmq.argmin(5..6), 5);
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = SmallRng::seed_from_u64(9);
+        for (len, max) in [(300, 5), (300, 1000), (5000, 50)] {
+            let values: Vec<u32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let rmq = Rmq::new(&values);
+            for _ in 0..2000 {
+                let start = rng.gen_range(0..len);
+                let width = rng.gen_range(1..200);
+                let end = rng.gen_range(start + 1..=len.min(start + width));
+                assert_eq!(rmq.argmin(start..end), naive_argmin(&values, start..end));
+            }
+        }
+        let sorted: Vec<u32> = (0..3000).collect();
+        let rmq = Rmq::new(&sorted);
+        assert_eq!(rmq.argmin(17..2999), 17);
+        let reversed: Vec<u32> = (0..3000).rev().collect();
+        let rmq = Rmq::new(&reversed);
+        assert_eq!(rmq.argmin(17..2999), 2998);
+    }
+}
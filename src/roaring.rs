@@ -0,0 +1,326 @@
+//! # RoaringBitmap
+//!
+//! A compressed set of 64 bit integers in the layout of
+//! [Roaring bitmaps](https://doi.org/10.1002/spe.2325), with rank and select. The values are split
+//! into chunks of `2^16` by their upper bits, and each chunk is stored in the smallest of three
+//! containers:
+//!
+//! * a sorted array of the lower 16 bits for few values,
+//! * a [`RunawayVector`] of `2^16` bits, whose L1 and L2 counters answer rank and select inside
+//!   the chunk,
+//! * a sorted list of runs for values which come in long intervals.
+//!
+//! The top level keeps the number of values in front of each chunk, so a rank is a binary search
+//! for the chunk and a rank inside it, and a select is a binary search over these counts.
+//!
+use std::mem;
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::runaway_vector::RunawayVector;
+use crate::space_report::{self, SpaceReport};
+
+const CHUNK_BITS: u32 = 16;
+const CHUNK_SIZE: usize = 1 << CHUNK_BITS;
+// Chunks with more values than this are cheaper as a bitmap than as an array.
+const MAX_ARRAY_LEN: usize = 4096;
+
+enum Container {
+    Array(Vec<u16>),
+    Bitmap(RunawayVector<'static>),
+    Runs {
+        // The first value and the length minus one of each run.
+        runs: Vec<(u16, u16)>,
+        // The number of values in front of each run, which fits into 16 bits as each run holds at
+        // least one value.
+        ranks: Vec<u16>,
+    },
+}
+
+impl Container {
+    fn new(lows: &[u16]) -> Self {
+        let runs = lows
+            .windows(2)
+            .filter(|pair| pair[0] + 1 != pair[1])
+            .count()
+            + 1;
+        // The sizes in bytes of the three containers, where each run also needs its rank.
+        let array = 2 * lows.len();
+        let bitmap = CHUNK_SIZE / 8;
+        if 6 * runs < array.min(bitmap) {
+            let mut list: Vec<(u16, u16)> = Vec::with_capacity(runs);
+            for low in lows {
+                match list.last_mut() {
+                    Some((start, len)) if *start as usize + *len as usize + 1 == *low as usize => {
+                        *len += 1
+                    }
+                    _ => list.push((*low, 0)),
+                }
+            }
+            let ranks = list
+                .iter()
+                .scan(0, |before, (_, len)| {
+                    let rank = *before;
+                    *before += *len as usize + 1;
+                    Some(rank as u16)
+                })
+                .collect();
+            Container::Runs { runs: list, ranks }
+        } else if lows.len() <= MAX_ARRAY_LEN {
+            Container::Array(lows.to_vec())
+        } else {
+            let mut bit_vec: BitVec<u64, Lsb0> = BitVec::repeat(false, CHUNK_SIZE);
+            for low in lows {
+                bit_vec.set(*low as usize, true);
+            }
+            Container::Bitmap(RunawayVector::from_owned(bit_vec))
+        }
+    }
+
+    // The number of values smaller than `low`.
+    fn rank(&self, low: u16) -> usize {
+        match self {
+            Container::Array(lows) => lows.partition_point(|value| *value < low),
+            Container::Bitmap(bits) => bits.rank1(low as usize),
+            Container::Runs { runs, ranks } => {
+                match runs.partition_point(|(start, _)| *start < low) {
+                    0 => 0,
+                    i => ranks[i - 1] as usize + run_rank(runs[i - 1].0, runs[i - 1].1, low),
+                }
+            }
+        }
+    }
+
+    fn contains(&self, low: u16) -> bool {
+        match self {
+            Container::Array(lows) => lows.binary_search(&low).is_ok(),
+            Container::Bitmap(bits) => bits.access(low as usize),
+            Container::Runs { runs, .. } => {
+                let i = runs.partition_point(|(start, _)| *start <= low);
+                i > 0 && low - runs[i - 1].0 <= runs[i - 1].1
+            }
+        }
+    }
+
+    // The `rank`-th value, starting with 1.
+    fn select(&self, rank: usize) -> u16 {
+        match self {
+            Container::Array(lows) => lows[rank - 1],
+            Container::Bitmap(bits) => bits.select1(rank).unwrap() as u16,
+            Container::Runs { runs, ranks } => {
+                // The last run with fewer values in front of it than the rank.
+                let i = ranks.partition_point(|before| (*before as usize) < rank) - 1;
+                let (start, len) = runs[i];
+                let offset = rank - 1 - ranks[i] as usize;
+                assert!(offset <= len as usize, "rank larger than the container");
+                start + offset as u16
+            }
+        }
+    }
+
+    fn space_usage(&self) -> usize {
+        match self {
+            Container::Array(lows) => lows.len() * 16,
+            Container::Bitmap(bits) => bits.len() + bits.space_usage(),
+            Container::Runs { runs, ranks } => runs.len() * 32 + ranks.len() * 16,
+        }
+    }
+
+    // The values in any of the representations are the data. The enum itself is stored in the
+    // vector of the containers.
+    fn space_report(&self) -> SpaceReport {
+        let report = match self {
+            Container::Array(lows) => SpaceReport {
+                data: space_report::bits(lows),
+                slack: space_report::slack(lows),
+                ..SpaceReport::default()
+            },
+            Container::Bitmap(bits) => bits.space_report().inline(),
+            Container::Runs { runs, ranks } => SpaceReport {
+                data: space_report::bits(runs),
+                other: space_report::bits(ranks),
+                slack: space_report::slack(runs) + space_report::slack(ranks),
+                ..SpaceReport::default()
+            },
+        };
+        SpaceReport::of::<Self>() + report
+    }
+}
+
+// The number of values of the run which are smaller than `low`.
+fn run_rank(start: u16, len: u16, low: u16) -> usize {
+    ((low - start) as usize).min(len as usize + 1)
+}
+
+pub struct RoaringBitmap {
+    // The upper bits of the values in each chunk.
+    keys: Vec<u64>,
+    // The number of values in front of each chunk.
+    offsets: Vec<usize>,
+    containers: Vec<Container>,
+    len: usize,
+}
+
+impl RoaringBitmap {
+    /// Builds the set of `values`. Duplicate values are stored once.
+    pub fn new(values: &[u64]) -> Self {
+        let mut values = values.to_vec();
+        values.sort_unstable();
+        values.dedup();
+
+        let mut keys = Vec::new();
+        let mut offsets = Vec::new();
+        let mut containers = Vec::new();
+        let mut offset = 0;
+        for chunk in values.chunk_by(|a, b| a >> CHUNK_BITS == b >> CHUNK_BITS) {
+            let lows: Vec<u16> = chunk.iter().map(|value| *value as u16).collect();
+            keys.push(chunk[0] >> CHUNK_BITS);
+            offsets.push(offset);
+            containers.push(Container::new(&lows));
+            offset += chunk.len();
+        }
+
+        RoaringBitmap {
+            keys,
+            offsets,
+            containers,
+            len: values.len(),
+        }
+    }
+
+    /// The number of values.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, value: u64) -> bool {
+        self.keys
+            .binary_search(&(value >> CHUNK_BITS))
+            .is_ok_and(|i| self.containers[i].contains(value as u16))
+    }
+
+    /// Counts the values smaller than `value`.
+    pub fn rank(&self, value: u64) -> usize {
+        match self.keys.binary_search(&(value >> CHUNK_BITS)) {
+            Ok(i) => self.offsets[i] + self.containers[i].rank(value as u16),
+            Err(i) => self.offsets.get(i).copied().unwrap_or(self.len),
+        }
+    }
+
+    /// Finds the `rank`-th smallest value, starting with 1.
+    pub fn select(&self, rank: usize) -> Option<u64> {
+        assert!(rank > 0);
+        if rank > self.len {
+            return None;
+        }
+        let i = self.offsets.partition_point(|offset| *offset < rank) - 1;
+        let low = self.containers[i].select(rank - self.offsets[i]);
+        Some(self.keys[i] << CHUNK_BITS | low as u64)
+    }
+
+    /// Iterates over the values in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        (1..=self.len).map(|rank| self.select(rank).unwrap())
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.containers
+            .iter()
+            .map(|container| container.space_usage())
+            .sum::<usize>()
+            + (self.keys.len() + self.offsets.len()) * 64
+            + mem::size_of::<Self>()
+    }
+
+    /// The keys and the offsets of the chunks are other support.
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            other: space_report::bits(&self.keys) + space_report::bits(&self.offsets),
+            slack: space_report::slack(&self.keys)
+                + space_report::slack(&self.offsets)
+                + space_report::slack(&self.containers),
+            ..SpaceReport::of::<Self>()
+        } + self.containers.iter().map(Container::space_report).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn check(values: &[u64]) -> RoaringBitmap {
+        let roaring = RoaringBitmap::new(values);
+        let mut sorted = values.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(roaring.len(), sorted.len());
+        for (i, value) in sorted.iter().enumerate() {
+            assert!(roaring.contains(*value));
+            assert_eq!(roaring.rank(*value), i);
+            if *value < u64::MAX {
+                assert_eq!(roaring.rank(*value + 1), i + 1);
+            }
+            assert_eq!(roaring.select(i + 1), Some(*value));
+        }
+        assert_eq!(roaring.select(sorted.len() + 1), None);
+        roaring
+    }
+
+    #[test]
+    fn containers() {
+        let mut values: Vec<u64> = (0..100).map(|i| i * 300).collect();
+        values.extend((1 << 16..2 << 16).filter(|value| value % 3 != 0));
+        values.extend((5 << 16) + 10..(5 << 16) + 20_000);
+        let roaring = check(&values);
+        assert!(matches!(roaring.containers[0], Container::Array(_)));
+        assert!(matches!(roaring.containers[1], Container::Bitmap(_)));
+        assert!(matches!(roaring.containers[2], Container::Runs { .. }));
+        assert!(!roaring.contains(3 << 16));
+        assert_eq!(roaring.rank(3 << 16), roaring.rank(5 << 16));
+        assert_eq!(roaring.rank(u64::MAX), values.len());
+    }
+
+    #[test]
+    fn many_runs() {
+        // Runs of up to 200 values with gaps, so that the chunk is stored as runs.
+        let mut rng = SmallRng::seed_from_u64(37);
+        let mut values: Vec<u64> = ((1 << 16) - 5..1 << 16).collect();
+        let mut value = 7 << 16;
+        while value < (8 << 16) - 250 {
+            let len = rng.gen_range(1..200);
+            values.extend(value..value + len);
+            value += len + rng.gen_range(1..50);
+        }
+        let roaring = check(&values);
+        assert!(roaring
+            .containers
+            .iter()
+            .all(|container| matches!(container, Container::Runs { .. })));
+        for value in (7 << 16)..(8 << 16) {
+            assert_eq!(roaring.rank(value), values.partition_point(|v| *v < value));
+        }
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = SmallRng::seed_from_u64(31);
+        let mut values: Vec<u64> = (0..20_000).map(|_| rng.gen_range(0..1 << 20)).collect();
+        values.extend((0..1000).map(|_| rng.gen::<u64>()));
+        let roaring = check(&values);
+        values.sort_unstable();
+        values.dedup();
+        for _ in 0..1000 {
+            let value = rng.gen_range(0..1 << 21);
+            assert_eq!(roaring.rank(value), values.partition_point(|v| *v < value));
+        }
+        assert!(roaring.iter().is_sorted());
+        assert!(RoaringBitmap::new(&[]).is_empty());
+    }
+}
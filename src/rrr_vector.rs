@@ -0,0 +1,377 @@
+//! # RrrVector
+//!
+//! A compressed bit vector with rank and select support as presented by
+//! [Raman, Raman and Rao](https://doi.org/10.1145/1290672.1290680). The bits are cut into blocks
+//! of `block_size` bits. Each block is stored as its class, the number of ones in it, and its
+//! offset, the index of the block among all blocks of the same class. The offsets need
+//! `log(binomial(block_size, class))` bits, so sparse and dense blocks are stored in few bits and
+//! the vector approaches the zero-order entropy `H0`.
+//!
+//! Every [`SUPERBLOCK_SIZE`] blocks the number of ones and the position of the next offset are
+//! sampled, so a rank query decodes at most one block after summing up the classes in front of it.
+//!
+use std::{cmp, mem};
+
+use bitvec::field::BitField;
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::rank_select::RankSelect;
+use crate::space_report::{self, SpaceReport};
+use crate::word::select_in_word;
+
+pub const SUPERBLOCK_SIZE: usize = 32;
+pub const MAX_BLOCK_SIZE: usize = 63;
+
+// `BINOMIALS[n][k]` is `n` choose `k`.
+static BINOMIALS: [[u64; MAX_BLOCK_SIZE + 1]; MAX_BLOCK_SIZE + 1] = binomials();
+
+pub struct RrrVector {
+    len: usize,
+    block_size: usize,
+    class_width: usize,
+    classes: BitVec<u64, Lsb0>,
+    offsets: BitVec<u64, Lsb0>,
+    // The number of ones and the start of the offsets in front of each superblock.
+    superblock_ranks: Vec<u64>,
+    superblock_offsets: Vec<u64>,
+}
+
+impl RrrVector {
+    pub fn new(bit_vec: &BitVec<u64, Lsb0>, block_size: usize) -> Self {
+        assert!(
+            (1..=MAX_BLOCK_SIZE).contains(&block_size),
+            "block size must be between 1 and {}",
+            MAX_BLOCK_SIZE
+        );
+        let class_width = (usize::BITS - block_size.leading_zeros()) as usize;
+        let block_count = bit_vec.len().div_ceil(block_size);
+        let mut classes = BitVec::with_capacity(block_count * class_width);
+        let mut offsets = BitVec::new();
+        let mut superblock_ranks = Vec::with_capacity(block_count / SUPERBLOCK_SIZE + 1);
+        let mut superblock_offsets = Vec::with_capacity(block_count / SUPERBLOCK_SIZE + 1);
+
+        let mut rank = 0;
+        for (i, chunk) in bit_vec.chunks(block_size).enumerate() {
+            if i % SUPERBLOCK_SIZE == 0 {
+                superblock_ranks.push(rank);
+                superblock_offsets.push(offsets.len() as u64);
+            }
+            let block: u64 = chunk.load_le();
+            let class = block.count_ones() as usize;
+            push_bits(&mut classes, class as u64, class_width);
+            push_bits(
+                &mut offsets,
+                encode(block, block_size),
+                offset_width(block_size, class),
+            );
+            rank += class as u64;
+        }
+        superblock_ranks.push(rank);
+        superblock_offsets.push(offsets.len() as u64);
+
+        RrrVector {
+            len: bit_vec.len(),
+            block_size,
+            class_width,
+            classes,
+            offsets,
+            superblock_ranks,
+            superblock_offsets,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn access(&self, idx: usize) -> bool {
+        assert!(idx < self.len);
+        let (_, block) = self.block(idx / self.block_size);
+        (block >> (idx % self.block_size)) & 1 == 1
+    }
+
+    pub fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank1(idx)
+    }
+
+    /// Counts the ones in front of `idx`. `idx` may be the length of the vector.
+    pub fn rank1(&self, idx: usize) -> usize {
+        assert!(idx <= self.len);
+        let block_pos = idx / self.block_size;
+        let (rank, block) = self.block(block_pos);
+        if idx.is_multiple_of(self.block_size) {
+            return rank;
+        }
+        rank + (block & ((1 << (idx % self.block_size)) - 1)).count_ones() as usize
+    }
+
+    pub fn select0(&self, rank: usize) -> Option<usize> {
+        self.select(rank, false)
+    }
+
+    pub fn select1(&self, rank: usize) -> Option<usize> {
+        self.select(rank, true)
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.classes.len()
+            + self.offsets.len()
+            + (self.superblock_ranks.len() + self.superblock_offsets.len()) * 64
+            + mem::size_of::<Self>()
+    }
+
+    /// The classes and offsets are the data, the sampled ranks of the superblocks are the L0
+    /// counters and their offset positions the other support.
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            l0: space_report::bits(&self.superblock_ranks),
+            other: space_report::bits(&self.superblock_offsets),
+            slack: space_report::slack(&self.superblock_ranks)
+                + space_report::slack(&self.superblock_offsets),
+            ..SpaceReport::of::<Self>()
+        } + space_report::bit_vec(&self.classes)
+            + space_report::bit_vec(&self.offsets)
+    }
+
+    fn select(&self, rank: usize, bit: bool) -> Option<usize> {
+        assert!(rank > 0);
+        // The number of ones or zeros in front of each superblock.
+        let count = |superblock: usize| {
+            let ones = self.superblock_ranks[superblock] as usize;
+            if bit {
+                ones
+            } else {
+                cmp::min(superblock * SUPERBLOCK_SIZE * self.block_size, self.len) - ones
+            }
+        };
+        if rank > count(self.superblock_ranks.len() - 1) {
+            return None;
+        }
+        // Binary search for the last superblock with less than `rank` ones or zeros in front of it.
+        let mut l = 0;
+        let mut r = self.superblock_ranks.len() - 1;
+        while l + 1 < r {
+            let m = (l + r) / 2;
+            if count(m) < rank {
+                l = m;
+            } else {
+                r = m;
+            }
+        }
+        let superblock = l;
+        let mut rank = rank - count(superblock);
+        let mut offset = self.superblock_offsets[superblock] as usize;
+        let mut block_pos = superblock * SUPERBLOCK_SIZE;
+        loop {
+            let class = self.class(block_pos);
+            let block_count = if bit {
+                class
+            } else {
+                self.block_len(block_pos) - class
+            };
+            if rank <= block_count {
+                let block = self.decode(offset, class);
+                let block = if bit { block } else { !block };
+                return Some(block_pos * self.block_size + select_in_word(block, rank));
+            }
+            rank -= block_count;
+            offset += offset_width(self.block_size, class);
+            block_pos += 1;
+        }
+    }
+
+    // The number of ones in front of the block and the decoded block.
+    fn block(&self, block_pos: usize) -> (usize, u64) {
+        let superblock = block_pos / SUPERBLOCK_SIZE;
+        let mut rank = self.superblock_ranks[superblock] as usize;
+        let mut offset = self.superblock_offsets[superblock] as usize;
+        for i in superblock * SUPERBLOCK_SIZE..block_pos {
+            let class = self.class(i);
+            rank += class;
+            offset += offset_width(self.block_size, class);
+        }
+        if block_pos * self.block_size >= self.len {
+            return (rank, 0);
+        }
+        (rank, self.decode(offset, self.class(block_pos)))
+    }
+
+    fn decode(&self, offset: usize, class: usize) -> u64 {
+        let width = offset_width(self.block_size, class);
+        // Classes with a single block do not store an offset.
+        if width == 0 {
+            return decode(0, self.block_size, class);
+        }
+        decode(
+            self.offsets[offset..offset + width].load_le(),
+            self.block_size,
+            class,
+        )
+    }
+
+    fn class(&self, block_pos: usize) -> usize {
+        let start = block_pos * self.class_width;
+        self.classes[start..start + self.class_width].load_le::<u64>() as usize
+    }
+
+    fn block_len(&self, block_pos: usize) -> usize {
+        cmp::min(self.block_size, self.len - block_pos * self.block_size)
+    }
+}
+
+impl RankSelect for RrrVector {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn access(&self, idx: usize) -> bool {
+        self.access(idx)
+    }
+
+    fn rank0(&self, idx: usize) -> usize {
+        self.rank0(idx)
+    }
+
+    fn rank1(&self, idx: usize) -> usize {
+        self.rank1(idx)
+    }
+
+    fn select0(&self, rank: usize) -> Option<usize> {
+        self.select0(rank)
+    }
+
+    fn select1(&self, rank: usize) -> Option<usize> {
+        self.select1(rank)
+    }
+
+    fn space_usage(&self) -> usize {
+        self.space_usage()
+    }
+
+    fn space_report(&self) -> SpaceReport {
+        self.space_report()
+    }
+}
+
+fn push_bits(bit_vec: &mut BitVec<u64, Lsb0>, value: u64, width: usize) {
+    if width > 0 {
+        let start = bit_vec.len();
+        bit_vec.resize(start + width, false);
+        bit_vec[start..].store_le(value);
+    }
+}
+
+// The number of bits needed for the offsets of a class.
+fn offset_width(block_size: usize, class: usize) -> usize {
+    let combinations = BINOMIALS[block_size][class];
+    (u64::BITS - (combinations - 1).leading_zeros()) as usize
+}
+
+// The position of `block` among all blocks with the same number of ones in the combinatorial
+// number system, where the `i`-th one at position `p` adds `p` choose `i`.
+fn encode(block: u64, block_size: usize) -> u64 {
+    let mut offset = 0;
+    let mut i = 1;
+    for (p, binomials) in BINOMIALS.iter().enumerate().take(block_size) {
+        if (block >> p) & 1 == 1 {
+            offset += binomials[i];
+            i += 1;
+        }
+    }
+    offset
+}
+
+fn decode(mut offset: u64, block_size: usize, class: usize) -> u64 {
+    let mut block = 0;
+    let mut p = block_size;
+    for i in (1..=class).rev() {
+        // Find the largest position whose binomial coefficient still fits into the offset.
+        p -= 1;
+        while BINOMIALS[p][i] > offset {
+            p -= 1;
+        }
+        block |= 1 << p;
+        offset -= BINOMIALS[p][i];
+    }
+    block
+}
+
+const fn binomials() -> [[u64; MAX_BLOCK_SIZE + 1]; MAX_BLOCK_SIZE + 1] {
+    let mut table = [[0; MAX_BLOCK_SIZE + 1]; MAX_BLOCK_SIZE + 1];
+    let mut n = 0;
+    while n <= MAX_BLOCK_SIZE {
+        table[n][0] = 1;
+        let mut k = 1;
+        while k <= n {
+            table[n][k] = table[n - 1][k - 1] + table[n - 1][k];
+            k += 1;
+        }
+        n += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Query;
+    use crate::rank_select::tests::{self, random_bit_vec};
+    use crate::runaway_vector::RunawayVector;
+
+    #[test]
+    fn encode_decode() {
+        for block_size in [1, 5, 15, 63] {
+            for block in [0, 1, 0b1011, (1 << block_size) - 1, 1 << (block_size - 1)] {
+                let block = block & ((1u128 << block_size) - 1) as u64;
+                let class = block.count_ones() as usize;
+                let offset = encode(block, block_size);
+                assert!(offset < BINOMIALS[block_size][class]);
+                assert_eq!(decode(offset, block_size, class), block);
+            }
+        }
+    }
+
+    #[test]
+    fn same_as_runaway() {
+        for (block_size, density) in [(15, 0.5), (31, 0.02), (63, 0.97), (7, 0.3), (1, 0.5)] {
+            let bv = random_bit_vec(10_000, density, block_size as u64);
+            tests::same_as_runaway(&RrrVector::new(&bv, block_size), &bv);
+        }
+    }
+
+    #[test]
+    fn process() {
+        let bv = random_bit_vec(3000, 0.4, 1);
+        let rrr = RrrVector::new(&bv, 31);
+        let runaway = RunawayVector::new(&bv);
+        for line in [
+            "access 17",
+            "rank 1 2999",
+            "rank 0 1000",
+            "select 1 5",
+            "select 0 77",
+        ] {
+            let query = Query::try_from(line).unwrap();
+            assert_eq!(
+                RankSelect::process(&rrr, &query).as_line(),
+                RankSelect::process(&runaway, &query).as_line()
+            );
+        }
+    }
+
+    #[test]
+    fn compresses_sparse() {
+        let bv = random_bit_vec(100_000, 0.01, 2);
+        let rrr = RrrVector::new(&bv, 63);
+        assert!(rrr.space_usage() < bv.len() / 4);
+    }
+}
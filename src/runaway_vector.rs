@@ -4,123 +4,450 @@
 //! [Zhou et al.](https://doi.org/10.1007/978-3-642-38527-8) It has a space overhead of `o(n)` and
 //! answers rank and select queries in `O(1)`.
 //!
-use std::{cmp, mem};
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut, Range};
+use std::{cmp, fmt, mem};
 
+use bitvec::field::BitField;
 use bitvec::order::Lsb0;
-use bitvec::prelude::BitVec;
+use bitvec::prelude::{BitSlice, BitVec};
 
+use crate::aligned::{Aligned, AlignedWords, Pages, Zeroable};
 use crate::query::{Query, QueryResult};
-use crate::query::Query::{Access, Rank, Select};
+use crate::rank_select::RankSelect;
+use crate::scan;
+use crate::space_report::{self, SpaceReport};
+use crate::word::{self, select_in_word};
 
-const L0_BIT_SIZE: usize = 1 << 32;
-const L1_BIT_SIZE: usize = 2048;
-const L2_BIT_SIZE: usize = 512;
+pub const L0_BIT_SIZE: usize = 1 << 32;
+pub const L1_BIT_SIZE: usize = 2048;
+pub const L2_BIT_SIZE: usize = 512;
 
-const L1_INDEX_BIT_SIZE: usize = 32;
-const L2_INDEX_BIT_SIZE: usize = 10;
+pub(crate) const L1_INDEX_BIT_SIZE: usize = 32;
+pub(crate) const L2_INDEX_BIT_SIZE: usize = 10;
 
-const L1_IN_L0_COUNT: usize = L0_BIT_SIZE / L1_BIT_SIZE;
+pub(crate) const L1_IN_L0_COUNT: usize = L0_BIT_SIZE / L1_BIT_SIZE;
 
-#[derive(Debug)]
-struct InterleavedIndex(u64, usize);
+/// The number of ones in front of a L1 block inside its L0 block in the lower 32 bits, followed by
+/// the number of ones in up to three of its L2 blocks with 10 bits each. The index is aligned to
+/// its size of 16 bytes, so that it never straddles two cache lines.
+#[derive(Clone, Copy)]
+#[repr(align(16))]
+pub(crate) struct InterleavedIndex(u64, usize);
+
+// Safety: Zero is valid for both integers and the alignment is 16 bytes.
+unsafe impl Zeroable for InterleavedIndex {}
 
 impl InterleavedIndex {
-    pub fn new(l1: u32, l2s: &[u16]) -> Self {
+    pub(crate) fn new(l1: u32, l2s: &[u16]) -> Self {
         assert!(l2s.len() < 4);
         let mut value = l1 as u64;
 
         for (i, l2) in l2s.iter().enumerate() {
-            value |= (*l2 as u64) << L2_INDEX_BIT_SIZE * i + L1_INDEX_BIT_SIZE
+            value |= (*l2 as u64) << (L2_INDEX_BIT_SIZE * i + L1_INDEX_BIT_SIZE)
         }
         InterleavedIndex(value, l2s.len())
     }
 
-    fn l1(&self) -> u32 {
+    pub(crate) fn l1(&self) -> u32 {
         self.0 as u32
     }
 
-    fn index(&self, index: usize) -> u16 {
+    pub(crate) fn index(&self, index: usize) -> u16 {
         assert!(index < self.1);
-        (self.0 >> ((L2_INDEX_BIT_SIZE * index) + L1_INDEX_BIT_SIZE) & (1 << L2_INDEX_BIT_SIZE) - 1)
-            as u16
+        ((self.0 >> ((L2_INDEX_BIT_SIZE * index) + L1_INDEX_BIT_SIZE))
+            & ((1 << L2_INDEX_BIT_SIZE) - 1)) as u16
     }
 
-    fn len(&self) -> usize {
+    pub(crate) fn len(&self) -> usize {
         self.1
     }
+
+    // The stored L2 counts with 10 bits each, the first one in the lowest bits.
+    pub(crate) fn packed_l2s(&self) -> u32 {
+        (self.0 >> L1_INDEX_BIT_SIZE) as u32
+    }
+}
+
+impl fmt::Debug for InterleavedIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let l2: Vec<u16> = (0..self.len()).map(|i| self.index(i)).collect();
+        f.debug_struct("InterleavedIndex")
+            .field("l1", &self.l1())
+            .field("l2", &l2)
+            .finish()
+    }
 }
 
 /// A succinct bit vector which supports rank and select queries in `O(1)` with a space usage in
 /// `o(n)`.
+///
+/// The bits are usually borrowed, but structures which are built on top of the vector can hand
+/// over their bits with [`RunawayVector::from_owned`]. Bits which were written to a file can be
+/// borrowed as words from its mapping with [`RunawayVector::from_words`].
 pub struct RunawayVector<'a> {
-    bit_vec: &'a BitVec<u64, Lsb0>,
+    bits: Bits<'a>,
+    index: RunawayIndex,
+    patterns: Vec<PatternIndex>,
+}
+
+// The storage of the bits of a vector.
+enum Bits<'a> {
+    Vec(Cow<'a, BitVec<u64, Lsb0>>),
+    // Words which start at the first bit, like those of a mapped file, and the number of bits.
+    Words(Cow<'a, [u64]>, usize),
+    // Words which start at a cache line, see `RunawayVector::with_pages`.
+    Aligned(AlignedWords, usize),
+}
+
+impl Bits<'_> {
+    fn as_bitslice(&self) -> &BitSlice<u64, Lsb0> {
+        match self {
+            Bits::Vec(bit_vec) => bit_vec,
+            Bits::Words(words, len) => &BitSlice::from_slice(words)[..*len],
+            Bits::Aligned(words, len) => &BitSlice::from_slice(words)[..*len],
+        }
+    }
+
+    fn words(&self) -> &[u64] {
+        match self {
+            Bits::Vec(bit_vec) => {
+                debug_assert!(word::is_aligned(bit_vec));
+                bit_vec.as_raw_slice()
+            }
+            Bits::Words(words, _) => words,
+            Bits::Aligned(words, _) => words,
+        }
+    }
+
+    fn is_aligned(&self) -> bool {
+        match self {
+            Bits::Vec(bit_vec) => word::is_aligned(bit_vec),
+            Bits::Words(..) | Bits::Aligned(..) => true,
+        }
+    }
+}
+
+/// The rank and select index of a [`RunawayVector`] without the bits. The index is built over and
+/// queried with [`Words`], so several indices can be built over views of the same words, like
+/// their complement or a masked view, without copying them. A vector over the plain bits can be
+/// assembled from its index with [`RunawayVector::with_index`].
+pub struct RunawayIndex {
+    len: usize,
+    ones: usize,
+    l12_indices: Counters,
+    l0_indices: Vec<u64>,
+}
+
+// The L1/L2 counters of an index, which are moved to a buffer that starts at a cache line by
+// `RunawayVector::with_pages`.
+enum Counters {
+    Vec(Vec<InterleavedIndex>),
+    Aligned(Aligned<InterleavedIndex>),
+}
+
+impl Deref for Counters {
+    type Target = [InterleavedIndex];
+
+    fn deref(&self) -> &[InterleavedIndex] {
+        match self {
+            Counters::Vec(counters) => counters,
+            Counters::Aligned(counters) => counters,
+        }
+    }
+}
+
+impl DerefMut for Counters {
+    fn deref_mut(&mut self) -> &mut [InterleavedIndex] {
+        match self {
+            Counters::Vec(counters) => counters,
+            Counters::Aligned(counters) => counters,
+        }
+    }
+}
+
+/// The words which an index is built over. Bit `i` is the bit `i % 64` of the word `i / 64`, so the
+/// raw words of a `BitVec` can only be used if its bits start at the lowest bit of its first word,
+/// which is not the case for a vector cut from another one. The bits of the last word behind the
+/// length of the index are ignored.
+pub trait Words {
+    fn word_count(&self) -> usize;
+
+    fn word(&self, i: usize) -> u64;
+}
+
+impl Words for [u64] {
+    fn word_count(&self) -> usize {
+        self.len()
+    }
+
+    fn word(&self, i: usize) -> u64 {
+        self[i]
+    }
+}
+
+/// The complement of shared words.
+pub struct Complement<'w>(pub &'w [u64]);
+
+impl Words for Complement<'_> {
+    fn word_count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn word(&self, i: usize) -> u64 {
+        !self.0[i]
+    }
+}
+
+/// The bits of shared words which are also set in a mask.
+pub struct Masked<'w> {
+    words: &'w [u64],
+    mask: &'w [u64],
+}
+
+impl<'w> Masked<'w> {
+    pub fn new(words: &'w [u64], mask: &'w [u64]) -> Self {
+        assert_eq!(words.len(), mask.len(), "words and mask differ in length");
+        Masked { words, mask }
+    }
+}
+
+impl Words for Masked<'_> {
+    fn word_count(&self) -> usize {
+        self.words.len()
+    }
+
+    fn word(&self, i: usize) -> u64 {
+        self.words[i] & self.mask[i]
+    }
+}
+
+/// The counters for the occurrences of a pattern of two bits, in the same layout as the counters
+/// for the ones. An occurrence is counted in the block of its first bit.
+struct PatternIndex {
+    pattern: [bool; 2],
     l12_indices: Vec<InterleavedIndex>,
     l0_indices: Vec<u64>,
+    count: usize,
 }
 
-impl<'a> RunawayVector<'a> {
-    pub fn new(bit_vec: &'a BitVec<u64, Lsb0>) -> Self {
-        let l0_capacity = (bit_vec.len() / L0_BIT_SIZE) + 1;
-        let l12_capacity = (bit_vec.len() / 64) + 1;
+/// Builds the index of a [`RunawayVector`] from the words of its bits, so that structures which
+/// compute the words one after another can index them in the same pass.
+pub(crate) struct IndexBuilder {
+    len: usize,
+    // The number of bits which were pushed.
+    pos: usize,
+    l0_indices: Vec<u64>,
+    l12_indices: Vec<InterleavedIndex>,
+    l0: u64,
+    // Wider than the stored L1 count, as a whole L0 block of ones would overflow it.
+    l1: u64,
+    l2s: [u16; 4],
+    l2_len: usize,
+    // The ones in the L2 block which is not complete yet.
+    l2: u16,
+}
 
-        let mut l0_indices: Vec<u64> = Vec::with_capacity(l0_capacity);
-        let mut l12_indices: Vec<InterleavedIndex> = Vec::with_capacity(l12_capacity);
+impl IndexBuilder {
+    pub(crate) fn new(len: usize) -> Self {
+        IndexBuilder {
+            len,
+            pos: 0,
+            l0_indices: Vec::with_capacity((len / L0_BIT_SIZE) + 1),
+            l12_indices: Vec::with_capacity((len / L1_BIT_SIZE) + 1),
+            l0: 0,
+            l1: 0,
+            l2s: [0; 4],
+            l2_len: 0,
+            l2: 0,
+        }
+    }
 
-        let mut l0: u64 = 0;
-        let mut l1: u32 = 0;
-        let mut l2s: [u16; 4] = [0; 4];
-        let mut l2_len = 0;
-        // Iterate over each L2 block
-        for (i, chunk) in bit_vec.chunks(L2_BIT_SIZE).enumerate() {
-            let l2_pos = i % 4;
-            l2s[l2_pos] = chunk.count_ones() as u16;
-            l2_len += 1;
-            // We are at the last l2 block and need to update the interleaved index.
-            if i % (L1_BIT_SIZE / L2_BIT_SIZE) == 3 {
-                l12_indices.push(InterleavedIndex::new(l1, &l2s[0..l2_len - 1]));
-                l1 = l1 + l2s[0] as u32 + l2s[1] as u32 + l2s[2] as u32 + l2s[3] as u32;
-                l2s = [0; 4];
-                l2_len = 0;
-            }
-            // We are at the end of the L0 block and need to append the L0 bit count
-            if i % (L0_BIT_SIZE / L2_BIT_SIZE) == (L0_BIT_SIZE / L2_BIT_SIZE) - 1 {
-                l0_indices.push(l0);
-                l0 += l1 as u64;
-                l1 = 0;
-            }
+    /// Counts the next word. The bits of the last word behind the length are ignored.
+    pub(crate) fn push(&mut self, word: u64) {
+        assert!(self.pos < self.len, "more words than bits");
+        let bits = cmp::min(64, self.len - self.pos);
+        let word = if bits < 64 {
+            word & ((1 << bits) - 1)
+        } else {
+            word
+        };
+        self.l2 += word.count_ones() as u16;
+        self.pos += bits;
+        if self.pos.is_multiple_of(L2_BIT_SIZE) || self.pos == self.len {
+            self.finish_l2();
+        }
+    }
+
+    fn finish_l2(&mut self) {
+        // The index of the L2 block in the whole vector.
+        let i = (self.pos - 1) / L2_BIT_SIZE;
+        self.l2s[i % 4] = self.l2;
+        self.l2 = 0;
+        self.l2_len += 1;
+        // We are at the last l2 block and need to update the interleaved index.
+        if i % (L1_BIT_SIZE / L2_BIT_SIZE) == 3 {
+            self.l12_indices.push(InterleavedIndex::new(
+                self.l1 as u32,
+                &self.l2s[0..self.l2_len - 1],
+            ));
+            self.l1 += self.l2s.iter().map(|l2| *l2 as u64).sum::<u64>();
+            self.l2s = [0; 4];
+            self.l2_len = 0;
+        }
+        // We are at the end of the L0 block and need to append the L0 bit count
+        if i % (L0_BIT_SIZE / L2_BIT_SIZE) == (L0_BIT_SIZE / L2_BIT_SIZE) - 1 {
+            self.l0_indices.push(self.l0);
+            self.l0 += self.l1;
+            self.l1 = 0;
+        }
+    }
+
+    /// Creates the vector from the bits whose words were pushed.
+    pub(crate) fn finish<'a>(self, bit_vec: Cow<'a, BitVec<u64, Lsb0>>) -> RunawayVector<'a> {
+        assert_eq!(bit_vec.len(), self.len);
+        RunawayVector {
+            bits: Bits::Vec(bit_vec),
+            index: self.finish_index(),
+            patterns: Vec::new(),
         }
+    }
+
+    /// Creates the index of the words which were pushed.
+    pub(crate) fn finish_index(mut self) -> RunawayIndex {
+        assert_eq!(self.pos, self.len, "missing words");
+        let l2s = &self.l2s[0..self.l2_len];
+        let ones = (self.l0 + self.l1) as usize + l2s.iter().map(|l2| *l2 as usize).sum::<usize>();
         // Fix, if the vector length < L1 block length
-        if bit_vec.len() % L1_BIT_SIZE != 0 {
-            l12_indices.push(InterleavedIndex::new(l1, &l2s[0..l2_len]));
+        if !self.len.is_multiple_of(L1_BIT_SIZE) {
+            self.l12_indices
+                .push(InterleavedIndex::new(self.l1 as u32, l2s));
         }
         // Fix, if the vector length < L0 block length
-        if bit_vec.len() % L0_BIT_SIZE != 0 {
-            l0_indices.push(l0);
+        if !self.len.is_multiple_of(L0_BIT_SIZE) {
+            self.l0_indices.push(self.l0);
         }
-        RunawayVector {
-            bit_vec,
-            l0_indices,
-            l12_indices,
+        RunawayIndex {
+            len: self.len,
+            ones,
+            l12_indices: Counters::Vec(self.l12_indices),
+            l0_indices: self.l0_indices,
         }
     }
+}
 
-    pub fn process(&self, query: &Query) -> QueryResult {
-        match query {
-            Access(idx) => QueryResult::Access(self.bit_vec[*idx]),
-            Rank(w, idx) => QueryResult::Rank(match *w {
-                true => self.rank1(*idx),
-                false => self.rank0(*idx),
-            }),
-            Select(w, nth) => QueryResult::Select(match *w {
-                true => self.select1(*nth),
-                false => self.select0(*nth),
-            }),
+impl fmt::Debug for RunawayIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunawayIndex")
+            .field("len", &self.len)
+            .field("ones", &self.ones)
+            .field("l0_entries", &self.l0_indices.len())
+            .field("l1_entries", &self.l12_indices.len())
+            .finish()
+    }
+}
+
+/// The breakdown of a L1 block, see [`RunawayVector::l1_block`]. Its `Debug` output shows the
+/// entries of the index next to the ones counted in the bits, so `{:#?}` prints one block per
+/// line and field.
+#[derive(Debug)]
+pub struct L1Block {
+    pub idx: usize,
+    pub bits: Range<usize>,
+    /// The ones in front of the L0 block.
+    pub l0: u64,
+    /// The ones in front of the block inside its L0 block.
+    pub l1: u32,
+    /// The stored counts of the L2 blocks.
+    pub l2: Vec<u16>,
+    /// The ones in each L2 block, counted in the bits.
+    pub counted: Vec<usize>,
+}
+
+/// The first entry of an index which does not match the bits, see [`RunawayIndex::verify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndexMismatch {
+    /// The index has a different number of entries than the bits need.
+    Entries,
+    /// The number of ones in the whole vector.
+    Ones { expected: usize, found: usize },
+    L0 {
+        idx: usize,
+        expected: u64,
+        found: u64,
+    },
+    L1 {
+        idx: usize,
+        expected: u32,
+        found: u32,
+    },
+    /// A L2 count, where `idx` counts the L2 blocks of the whole vector.
+    L2 {
+        idx: usize,
+        expected: u16,
+        found: u16,
+    },
+}
+
+// The number of L1 blocks which are checked by `RunawayIndex::verify_quick`.
+const QUICK_SAMPLES: usize = 64;
+
+impl RunawayIndex {
+    /// Builds the index of the first `len` bits of `words`, which must start at bit 0 of the
+    /// vector, see [`Words`].
+    pub fn new<W: Words + ?Sized>(words: &W, len: usize) -> Self {
+        assert_eq!(
+            words.word_count(),
+            len.div_ceil(64),
+            "words differ in length"
+        );
+        let mut builder = IndexBuilder::new(len);
+        for i in 0..words.word_count() {
+            builder.push(words.word(i));
         }
+        builder.finish_index()
+    }
+
+    /// The number of bits the index was built for.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of ones in the indexed words.
+    pub fn ones(&self) -> usize {
+        self.ones
+    }
+
+    /// The ones in front of each L0 block.
+    pub fn l0_entries(&self) -> &[u64] {
+        &self.l0_indices
     }
 
-    pub fn select0(&self, mut rank: usize) -> Option<usize> {
+    /// The number of L1 entries. It can be one larger than the number of L1 blocks, as a vector
+    /// whose last L1 block holds four L2 blocks gets an additional entry behind it.
+    pub fn l1_entry_count(&self) -> usize {
+        self.l12_indices.len()
+    }
+
+    /// The ones in front of the L1 block `i` inside its L0 block.
+    pub fn l1_entry(&self, i: usize) -> u32 {
+        self.l12_indices[i].l1()
+    }
+
+    /// The ones in the stored L2 blocks of the L1 block `i`. The count of the fourth L2 block is
+    /// not stored, as it follows from the next L1 entry.
+    pub fn l2_counts(&self, i: usize) -> Vec<u16> {
+        let l12 = &self.l12_indices[i];
+        (0..l12.len()).map(|k| l12.index(k)).collect()
+    }
+
+    pub fn select0<W: Words + ?Sized>(&self, words: &W, mut rank: usize) -> Option<usize> {
         assert!(rank > 0);
-        assert!(rank <= self.bit_vec.len());
+        assert!(rank <= self.len);
         let mut l0_pos = 0;
         // As the indices store the amount of ones we need to subtract each index from
         // the total amount of bits ot get the amount of zeros inside a block.
@@ -152,54 +479,24 @@ impl<'a> RunawayVector<'a> {
         let l1_pos = r;
         let l12_index = &self.l12_indices[l1_pos];
         // Same problem as with L0 index.
-        if l1_pos % L1_IN_L0_COUNT != 0 {
+        if !l1_pos.is_multiple_of(L1_IN_L0_COUNT) {
             rank -= (l1_pos % L1_IN_L0_COUNT) * L1_BIT_SIZE - l12_index.l1() as usize
         }
-        let mut l2_pos = 0;
-        for i in 0..l12_index.len() {
-            // In contrast to L0 and L1 indices L2 indices contain the number of ones in a single block
-            // and are not incremental. We therefor calculate the number of zeros by subtracting
-            // the number of ones from the L2 size.
-            if rank > L2_BIT_SIZE - l12_index.index(i) as usize {
-                rank -= L2_BIT_SIZE - l12_index.index(i) as usize;
-                l2_pos += 1;
-            } else {
-                break;
-            }
-        }
-        let bit_search_start = l1_pos * L1_BIT_SIZE + (l2_pos) * L2_BIT_SIZE;
-        let bit_search_end = cmp::min(bit_search_start + L1_BIT_SIZE, self.bit_vec.len());
-
-        let mut u64_pos = 0;
-        while rank > 64 && u64_pos < 8 {
-            let ones = self.bit_vec
-                [bit_search_start + 64 * u64_pos..bit_search_start + 64 * (u64_pos + 1)]
-                .count_zeros();
-            rank -= ones;
-            u64_pos += 1;
-        }
-
-        let mut bit = 0;
-        for i in bit_search_start + 64 * u64_pos..bit_search_end {
-            if !self.bit_vec[i] {
-                rank -= 1;
-                if rank == 0 {
-                    bit = i;
-                    break;
-                }
-            }
-        }
-        if rank != 0 {
-            debug_assert!(false, "rank too large");
-            None
-        } else {
-            Some(bit)
-        }
+        // In contrast to L0 and L1 indices L2 indices contain the number of ones in a single block
+        // and are not incremental. We therefor calculate the number of zeros by subtracting
+        // the number of ones from the L2 size.
+        let (l2_pos, rank) = scan::l2_block(l12_index, rank, false);
+        self.select_in_l2(
+            words,
+            l1_pos * L1_BIT_SIZE + l2_pos * L2_BIT_SIZE,
+            rank,
+            false,
+        )
     }
 
-    pub fn select1(&self, mut rank: usize) -> Option<usize> {
+    pub fn select1<W: Words + ?Sized>(&self, words: &W, mut rank: usize) -> Option<usize> {
         assert!(rank > 0);
-        assert!(rank <= self.bit_vec.len());
+        assert!(rank <= self.len);
         let mut l0_pos = 0;
         // Search the L0 index with a linear search from the first ot the last L0 index.
         // If the L0 index is at one point larger than the queried rank, we use the previous
@@ -228,59 +525,59 @@ impl<'a> RunawayVector<'a> {
         let l1_pos = r;
         let l12_index = &self.l12_indices[l1_pos];
         rank -= l12_index.l1() as usize;
-        let mut l2_pos = 0;
-        for i in 0..l12_index.len() {
-            // As long as our L2 index is smaller than our rank, we subtract the number of ones
-            // from our rank and look at the next L2 block.
-            if rank > l12_index.index(i) as usize {
-                rank -= l12_index.index(i) as usize;
-                l2_pos += 1;
-            } else {
-                break;
-            }
-        }
+        // Find the L2 block in which the rank is larger than the ones in front of it.
+        let (l2_pos, rank) = scan::l2_block(l12_index, rank, true);
+        self.select_in_l2(
+            words,
+            l1_pos * L1_BIT_SIZE + l2_pos * L2_BIT_SIZE,
+            rank,
+            true,
+        )
+    }
 
-        // We are now inside a L2 block and do a linear search for the position of the bit.
-        let bit_search_start = l1_pos * L1_BIT_SIZE + (l2_pos) * L2_BIT_SIZE;
-        let bit_search_end = cmp::min(bit_search_start + L1_BIT_SIZE, self.bit_vec.len());
-
-        // Fast 64 bit search
-        let mut u64_pos = 0;
-        while rank > 64 && u64_pos < 8 {
-            let ones = self.bit_vec
-                [bit_search_start + 64 * u64_pos..bit_search_start + 64 * (u64_pos + 1)]
-                .count_ones();
-            rank -= ones;
-            u64_pos += 1;
-        }
-
-        // Slow search inside a 64 bit block
-        let mut bit = 0;
-        for i in bit_search_start + 64 * u64_pos..bit_search_end {
-            if self.bit_vec[i] {
-                rank -= 1;
-                if rank == 0 {
-                    bit = i;
-                    break;
-                }
+    // Finds the `rank`-th one or zero in the L2 block starting at `start`, first the word holding
+    // it and then the bit inside the word.
+    fn select_in_l2<W: Words + ?Sized>(
+        &self,
+        words: &W,
+        start: usize,
+        rank: usize,
+        bit: bool,
+    ) -> Option<usize> {
+        let mut block = [0; L2_BIT_SIZE / 64];
+        for (i, word) in block.iter_mut().enumerate() {
+            let idx = start + 64 * i;
+            if idx < self.len {
+                let data = words.word(idx / 64);
+                let data = if bit { data } else { !data };
+                // The bits behind the end of the vector must not be found.
+                let end = cmp::min(self.len - idx, 64);
+                *word = data & (u64::MAX >> (64 - end));
             }
         }
-        // If the rank is not 0 we missed some indices/bits and the algorithm is broken or the
+        // If the rank is not found we missed some indices/bits and the algorithm is broken or the
         // queried bit with such a rank does not exist.
-        if rank != 0 {
-            debug_assert!(false, "rank too large");
-            None
-        } else {
-            Some(bit)
+        match scan::word_in_block(&block, rank) {
+            Some((word, rank)) => Some(start + 64 * word + select_in_word(block[word], rank)),
+            None => {
+                debug_assert!(false, "rank too large");
+                None
+            }
         }
     }
 
-    pub fn rank0(&self, idx: usize) -> usize {
-        idx - self.rank1(idx)
+    pub fn rank0<W: Words + ?Sized>(&self, words: &W, idx: usize) -> usize {
+        idx - self.rank1(words, idx)
     }
 
-    pub fn rank1(&self, idx: usize) -> usize {
-        assert!(idx < self.bit_vec.len());
+    /// Counts the ones in front of `idx`. `idx` may be the length of the vector, in which case all
+    /// ones are counted.
+    pub fn rank1<W: Words + ?Sized>(&self, words: &W, idx: usize) -> usize {
+        assert!(idx <= self.len);
+        // The indices for the position behind the last bit might not exist.
+        if idx == self.len {
+            return self.ones;
+        }
         let l0_pos = idx / L0_BIT_SIZE;
         let l1_pos = idx / L1_BIT_SIZE;
         let l2_pos = (idx / L2_BIT_SIZE) % 4;
@@ -293,12 +590,872 @@ impl<'a> RunawayVector<'a> {
         for i in 0..l2_pos {
             l2 += l12.index(i) as usize;
         }
-        let hand_counted = self.bit_vec[idx - bit_pos..idx].count_ones();
+        let mut hand_counted: usize = ((idx - bit_pos) / 64..idx / 64)
+            .map(|i| words.word(i).count_ones() as usize)
+            .sum();
+        if !idx.is_multiple_of(64) {
+            hand_counted += (words.word(idx / 64) & ((1 << (idx % 64)) - 1)).count_ones() as usize;
+        }
         l0 + l1 + l2 + hand_counted
     }
 
+    /// Recomputes all counts from `words` and reports the first entry, in the order of the bits,
+    /// which does not match them.
+    pub fn verify<W: Words + ?Sized>(&self, words: &W) -> Result<(), IndexMismatch> {
+        if words.word_count() != self.len.div_ceil(64) {
+            return Err(IndexMismatch::Entries);
+        }
+        let expected = RunawayIndex::new(words, self.len);
+        if self.l0_indices.len() != expected.l0_indices.len()
+            || self.l12_indices.len() != expected.l12_indices.len()
+        {
+            return Err(IndexMismatch::Entries);
+        }
+        for (i, (found, expected_l12)) in self
+            .l12_indices
+            .iter()
+            .zip(expected.l12_indices.iter())
+            .enumerate()
+        {
+            if i.is_multiple_of(L1_IN_L0_COUNT) {
+                let idx = i / L1_IN_L0_COUNT;
+                if self.l0_indices[idx] != expected.l0_indices[idx] {
+                    return Err(IndexMismatch::L0 {
+                        idx,
+                        expected: expected.l0_indices[idx],
+                        found: self.l0_indices[idx],
+                    });
+                }
+            }
+            if found.l1() != expected_l12.l1() {
+                return Err(IndexMismatch::L1 {
+                    idx: i,
+                    expected: expected_l12.l1(),
+                    found: found.l1(),
+                });
+            }
+            if found.len() != expected_l12.len() {
+                return Err(IndexMismatch::Entries);
+            }
+            for k in 0..found.len() {
+                if found.index(k) != expected_l12.index(k) {
+                    return Err(IndexMismatch::L2 {
+                        idx: i * (L1_BIT_SIZE / L2_BIT_SIZE) + k,
+                        expected: expected_l12.index(k),
+                        found: found.index(k),
+                    });
+                }
+            }
+        }
+        if self.ones != expected.ones {
+            return Err(IndexMismatch::Ones {
+                expected: expected.ones,
+                found: self.ones,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the index against `words` in a few evenly spread L1 blocks and the last one. In each
+    /// of them the L2 counts are compared with the bits, and the ones of the whole block with the
+    /// difference to the next L1 or L0 count, so a wrong entry is only found close to the samples.
+    pub fn verify_quick<W: Words + ?Sized>(&self, words: &W) -> Result<(), IndexMismatch> {
+        // A vector whose last L1 block holds four L2 blocks has an additional entry behind it.
+        let blocks = self.len.div_ceil(L1_BIT_SIZE);
+        if words.word_count() != self.len.div_ceil(64)
+            || self.l0_indices.len() < self.len.div_ceil(L0_BIT_SIZE)
+            || self.l12_indices.len() < blocks
+        {
+            return Err(IndexMismatch::Entries);
+        }
+        let step = cmp::max(1, blocks / QUICK_SAMPLES);
+        for i in (0..blocks).step_by(step).chain(blocks.checked_sub(1)) {
+            let l12 = &self.l12_indices[i];
+            let start = i * L1_BIT_SIZE;
+            let l2_blocks = (cmp::min(start + L1_BIT_SIZE, self.len) - start).div_ceil(L2_BIT_SIZE);
+            if l12.len() != cmp::min(l2_blocks, 3) {
+                return Err(IndexMismatch::Entries);
+            }
+            let mut ones = 0;
+            for k in 0..l2_blocks {
+                let l2_start = start + k * L2_BIT_SIZE;
+                let count = count_ones(words, l2_start, cmp::min(l2_start + L2_BIT_SIZE, self.len));
+                if k < l12.len() && l12.index(k) as usize != count {
+                    return Err(IndexMismatch::L2 {
+                        idx: i * (L1_BIT_SIZE / L2_BIT_SIZE) + k,
+                        expected: count as u16,
+                        found: l12.index(k),
+                    });
+                }
+                ones += count;
+            }
+            // The ones in front of the end of the block inside its L0 block.
+            let after = l12.l1() as u64 + ones as u64;
+            let l0 = self.l0_indices[i / L1_IN_L0_COUNT];
+            if i + 1 == blocks {
+                if (l0 + after) as usize != self.ones {
+                    return Err(IndexMismatch::Ones {
+                        expected: (l0 + after) as usize,
+                        found: self.ones,
+                    });
+                }
+            } else if (i + 1).is_multiple_of(L1_IN_L0_COUNT) {
+                let idx = (i + 1) / L1_IN_L0_COUNT;
+                if self.l0_indices[idx] != l0 + after {
+                    return Err(IndexMismatch::L0 {
+                        idx,
+                        expected: l0 + after,
+                        found: self.l0_indices[idx],
+                    });
+                }
+            } else if self.l12_indices[i + 1].l1() as u64 != after {
+                return Err(IndexMismatch::L1 {
+                    idx: i + 1,
+                    expected: after as u32,
+                    found: self.l12_indices[i + 1].l1(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// A FNV-1a checksum over the L0 and L1/L2 counters, to compare an index with a copy of it.
+    pub fn checksum(&self) -> u64 {
+        const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0100_0000_01b3;
+        self.l0_indices
+            .iter()
+            .copied()
+            .chain(
+                self.l12_indices
+                    .iter()
+                    .flat_map(|l12| [l12.0, l12.1 as u64]),
+            )
+            .flat_map(u64::to_le_bytes)
+            .fold(OFFSET, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(PRIME)
+            })
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.counters_size() + mem::size_of::<Self>()
+    }
+
+    /// The counters as they are allocated, where each L1/L2 entry takes 16 bytes for its alignment.
+    /// Counters which start at a cache line report the unused end of their last line as padding.
+    pub fn space_report(&self) -> SpaceReport {
+        let l12 = space_report::bits(&self.l12_indices);
+        let (padding, slack) = match &self.l12_indices {
+            Counters::Vec(counters) => (0, space_report::slack(counters)),
+            Counters::Aligned(counters) => (counters.line_bits() - l12, 0),
+        };
+        SpaceReport {
+            l0: space_report::bits(&self.l0_indices),
+            l12,
+            padding,
+            slack: space_report::slack(&self.l0_indices) + slack,
+            ..SpaceReport::of::<Self>()
+        }
+    }
+
+    // The space used by the counters in bits, where each L1/L2 entry takes 16 bytes.
+    fn counters_size(&self) -> usize {
+        space_report::bits(&self.l12_indices) + space_report::bits(&self.l0_indices)
+    }
+}
+
+// Counts the ones of `words` in `start..end`, where `start` is the first bit of a word.
+fn count_ones<W: Words + ?Sized>(words: &W, start: usize, end: usize) -> usize {
+    debug_assert!(start.is_multiple_of(64));
+    (start..end)
+        .step_by(64)
+        .map(|pos| {
+            let bits = cmp::min(64, end - pos);
+            (words.word(pos / 64) & (u64::MAX >> (64 - bits))).count_ones() as usize
+        })
+        .sum()
+}
+
+impl<'a> RunawayVector<'a> {
+    /// Builds the index over borrowed bits. Bits which do not start at the lowest bit of their
+    /// first word, like a slice of another vector, are copied into an aligned vector first.
+    pub fn new(bit_vec: &'a BitVec<u64, Lsb0>) -> Self {
+        Self::build(Cow::Borrowed(bit_vec))
+    }
+
+    /// Builds the index over a bit vector which is then owned by the returned vector.
+    pub fn from_owned(bit_vec: BitVec<u64, Lsb0>) -> RunawayVector<'static> {
+        RunawayVector::build(Cow::Owned(bit_vec))
+    }
+
+    /// Builds the index over the first `len` bits of `words`, which are not copied if they are
+    /// borrowed, like the words of a file mapped with [`crate::serialize::map`]. Bit `i` is the bit
+    /// `i % 64` of the word `i / 64`.
+    pub fn from_words(words: Cow<'a, [u64]>, len: usize) -> Self {
+        assert_eq!(words.len(), len.div_ceil(64), "words differ in length");
+        let mut builder = IndexBuilder::new(len);
+        for word in words.iter() {
+            builder.push(*word);
+        }
+        RunawayVector {
+            bits: Bits::Words(words, len),
+            index: builder.finish_index(),
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Copies the bits into a buffer which starts at a cache line and moves the L1/L2 counters of
+    /// the index into one as well, so that neither a L2 block nor its counter straddles two cache
+    /// lines. With [`Pages::Huge`] both buffers are backed by huge pages if the system supports
+    /// them, see [`crate::aligned`].
+    pub fn with_pages(bit_vec: &BitSlice<u64, Lsb0>, pages: Pages) -> RunawayVector<'static> {
+        let mut words = AlignedWords::zeroed(bit_vec.len().div_ceil(64), pages);
+        let mut builder = IndexBuilder::new(bit_vec.len());
+        // The words are loaded from the bits, so bits which start inside their first word are
+        // moved to the start of the buffer.
+        for (word, chunk) in words.iter_mut().zip(bit_vec.chunks(64)) {
+            *word = chunk.load_le();
+            builder.push(*word);
+        }
+        let mut index = builder.finish_index();
+        index.l12_indices = Counters::Aligned(Aligned::from_slice(&index.l12_indices, pages));
+        RunawayVector {
+            bits: Bits::Aligned(words, bit_vec.len()),
+            index,
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Whether the bits are backed by huge pages, see [`AlignedWords::is_mapped`].
+    pub fn is_mapped(&self) -> bool {
+        matches!(&self.bits, Bits::Aligned(words, _) if words.is_mapped())
+    }
+
+    /// Assembles a vector from its bits and an index which was built over them with
+    /// [`RunawayIndex::new`].
+    ///
+    /// # Panics
+    ///
+    /// If the index was built for a different number of bits, or if the bits do not start at the
+    /// lowest bit of their first word, as the index reads their raw words.
+    pub fn with_index(bit_vec: &'a BitVec<u64, Lsb0>, index: RunawayIndex) -> Self {
+        assert_eq!(
+            bit_vec.len(),
+            index.len,
+            "index was built for a different length"
+        );
+        assert!(
+            word::is_aligned(bit_vec),
+            "bits do not start at the first bit of a word"
+        );
+        RunawayVector {
+            bits: Bits::Vec(Cow::Borrowed(bit_vec)),
+            index,
+            patterns: Vec::new(),
+        }
+    }
+
+    /// The rank and select index over the bits.
+    pub fn index(&self) -> &RunawayIndex {
+        &self.index
+    }
+
+    /// The entries of the index for the L1 block `i` and the ones counted in its bits.
+    pub fn l1_block(&self, i: usize) -> L1Block {
+        let start = i * L1_BIT_SIZE;
+        let end = cmp::min(start + L1_BIT_SIZE, self.len());
+        let words = self.words();
+        L1Block {
+            idx: i,
+            bits: start..end,
+            l0: self.index.l0_indices[i / L1_IN_L0_COUNT],
+            l1: self.index.l1_entry(i),
+            l2: self.index.l2_counts(i),
+            counted: (start..end)
+                .step_by(L2_BIT_SIZE)
+                .map(|l2| count_ones(words, l2, cmp::min(l2 + L2_BIT_SIZE, end)))
+                .collect(),
+        }
+    }
+
+    /// Checks the whole index against the bits, see [`RunawayIndex::verify`].
+    pub fn verify(&self) -> Result<(), IndexMismatch> {
+        assert!(self.bits.is_aligned(), "bits are not aligned");
+        self.index.verify(self.words())
+    }
+
+    /// Checks samples of the index against the bits, see [`RunawayIndex::verify_quick`].
+    pub fn verify_quick(&self) -> Result<(), IndexMismatch> {
+        assert!(self.bits.is_aligned(), "bits are not aligned");
+        self.index.verify_quick(self.words())
+    }
+
+    /// The checksum of the index, see [`RunawayIndex::checksum`].
+    pub fn checksum(&self) -> u64 {
+        self.index.checksum()
+    }
+
+    fn build(mut bit_vec: Cow<'a, BitVec<u64, Lsb0>>) -> Self {
+        // The index and the queries read the raw words, so bits which start inside their first
+        // word are copied to the start of it.
+        if !word::is_aligned(&bit_vec) {
+            bit_vec.to_mut().force_align();
+        }
+        let mut builder = IndexBuilder::new(bit_vec.len());
+        for word in bit_vec.as_raw_slice() {
+            builder.push(*word);
+        }
+        builder.finish(bit_vec)
+    }
+
+    /// Finds the first one at or after `idx`. The word of `idx` is scanned first and the index is
+    /// only used if the one is behind it.
+    pub fn next_one(&self, idx: usize) -> Option<usize> {
+        self.next(idx, true)
+    }
+
+    /// Finds the last one at or before `idx`.
+    pub fn prev_one(&self, idx: usize) -> Option<usize> {
+        self.prev(idx, true)
+    }
+
+    /// Finds the first zero at or after `idx`.
+    pub fn next_zero(&self, idx: usize) -> Option<usize> {
+        self.next(idx, false)
+    }
+
+    /// Finds the last zero at or before `idx`.
+    pub fn prev_zero(&self, idx: usize) -> Option<usize> {
+        self.prev(idx, false)
+    }
+
+    fn next(&self, idx: usize, bit: bool) -> Option<usize> {
+        assert!(idx < self.len());
+        let word = idx / 64;
+        let matches = self.word(word, bit) & (u64::MAX << (idx % 64));
+        if matches != 0 {
+            // The bits behind the end of the vector are not guaranteed to be anything.
+            return Some(word * 64 + matches.trailing_zeros() as usize)
+                .filter(|pos| *pos < self.len());
+        }
+        let next_word = (word + 1) * 64;
+        if next_word >= self.len() {
+            return None;
+        }
+        if bit {
+            let rank = self.rank1(next_word);
+            (rank < self.index.ones)
+                .then(|| self.select1(rank + 1))
+                .flatten()
+        } else {
+            let rank = self.rank0(next_word);
+            (rank < self.len() - self.index.ones)
+                .then(|| self.select0(rank + 1))
+                .flatten()
+        }
+    }
+
+    fn prev(&self, idx: usize, bit: bool) -> Option<usize> {
+        assert!(idx < self.len());
+        let word = idx / 64;
+        let matches = self.word(word, bit) & (u64::MAX >> (63 - idx % 64));
+        if matches != 0 {
+            return Some(word * 64 + 63 - matches.leading_zeros() as usize);
+        }
+        let rank = match bit {
+            true => self.rank1(word * 64),
+            false => self.rank0(word * 64),
+        };
+        match (rank, bit) {
+            (0, _) => None,
+            (rank, true) => self.select1(rank),
+            (rank, false) => self.select0(rank),
+        }
+    }
+
+    /// Iterates over the positions of the ones, a word at a time.
+    pub fn iter_ones(&self) -> Positions<'_> {
+        Positions::new(self.words(), true, 0..self.len())
+    }
+
+    /// Iterates over the positions of the zeros, a word at a time.
+    pub fn iter_zeros(&self) -> Positions<'_> {
+        Positions::new(self.words(), false, 0..self.len())
+    }
+
+    /// Iterates over the positions of the ones at or after `idx`.
+    pub fn iter_ones_from(&self, idx: usize) -> Positions<'_> {
+        assert!(idx <= self.len());
+        Positions::new(self.words(), true, idx..self.len())
+    }
+
+    /// A view of the bits in `range`.
+    pub fn range(&self, range: Range<usize>) -> RangeView<'_> {
+        assert!(range.start <= range.end && range.end <= self.len());
+        RangeView {
+            words: self.words(),
+            range,
+        }
+    }
+
+    // The word with index `word`, inverted when looking for zeros.
+    fn word(&self, word: usize, bit: bool) -> u64 {
+        let word = self.words()[word];
+        if bit {
+            word
+        } else {
+            !word
+        }
+    }
+
+    /// Adds the counters for `pattern`, which are needed by [`RunawayVector::rank_pattern`] and
+    /// [`RunawayVector::select_pattern`]. The first bit of the pattern is the one at the lower
+    /// position, so `[true, false]` is a one followed by a zero.
+    pub fn with_pattern(mut self, pattern: [bool; 2]) -> Self {
+        if self.has_pattern(pattern) {
+            return self;
+        }
+        let limit = self.pattern_limit();
+        let mut l0_indices = Vec::with_capacity(limit / L0_BIT_SIZE + 1);
+        let mut l12_indices = Vec::with_capacity(limit / L1_BIT_SIZE + 1);
+        let mut l0: u64 = 0;
+        // Wider than the stored L1 count, as a whole L0 block of occurrences would overflow it.
+        let mut l1: u64 = 0;
+        for l1_start in (0..limit).step_by(L1_BIT_SIZE) {
+            if l1_start.is_multiple_of(L0_BIT_SIZE) {
+                l0 += l1;
+                l1 = 0;
+                l0_indices.push(l0);
+            }
+            let l1_end = cmp::min(l1_start + L1_BIT_SIZE, limit);
+            let l2s: Vec<u16> = (l1_start..l1_end)
+                .step_by(L2_BIT_SIZE)
+                .map(|l2_start| {
+                    let l2_end = cmp::min(l2_start + L2_BIT_SIZE, l1_end);
+                    self.count_pattern(pattern, l2_start, l2_end) as u16
+                })
+                .collect();
+            l12_indices.push(InterleavedIndex::new(
+                l1 as u32,
+                &l2s[..cmp::min(l2s.len(), 3)],
+            ));
+            l1 += l2s.iter().map(|l2| *l2 as u64).sum::<u64>();
+        }
+        self.patterns.push(PatternIndex {
+            pattern,
+            l12_indices,
+            l0_indices,
+            count: (l0 + l1) as usize,
+        });
+        self
+    }
+
+    /// Whether the counters for `pattern` were added with [`RunawayVector::with_pattern`].
+    pub fn has_pattern(&self, pattern: [bool; 2]) -> bool {
+        self.patterns.iter().any(|index| index.pattern == pattern)
+    }
+
+    /// The number of bits in the vector.
+    pub fn len(&self) -> usize {
+        match &self.bits {
+            Bits::Vec(bit_vec) => bit_vec.len(),
+            Bits::Words(_, len) | Bits::Aligned(_, len) => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The underlying bits.
+    pub fn bit_vec(&self) -> &BitSlice<u64, Lsb0> {
+        self.bits.as_bitslice()
+    }
+
+    // The raw words of the bits, which start at bit 0 as the bits are aligned when indexed.
+    pub(crate) fn words(&self) -> &[u64] {
+        self.bits.words()
+    }
+
+    pub fn access(&self, idx: usize) -> bool {
+        self.bit_vec()[idx]
+    }
+
+    /// Answers `query` like every [`RankSelect`] does, so callers do not need the trait in scope.
+    pub fn process(&self, query: &Query) -> QueryResult {
+        RankSelect::process(self, query)
+    }
+
+    pub fn select0(&self, rank: usize) -> Option<usize> {
+        self.index.select0(self.words(), rank)
+    }
+
+    pub fn select1(&self, rank: usize) -> Option<usize> {
+        self.index.select1(self.words(), rank)
+    }
+
+    pub fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank1(idx)
+    }
+
+    /// Counts the ones in front of `idx`. `idx` may be the length of the vector, in which case all
+    /// ones are counted.
+    pub fn rank1(&self, idx: usize) -> usize {
+        self.index.rank1(self.words(), idx)
+    }
+
+    /// Counts the ones in `range`. Short ranges are counted directly instead of with two ranks.
+    pub fn count_ones(&self, range: Range<usize>) -> usize {
+        assert!(range.start <= range.end && range.end <= self.len());
+        if range.len() <= L2_BIT_SIZE {
+            self.bit_vec()[range].count_ones()
+        } else {
+            self.rank1(range.end) - self.rank1(range.start)
+        }
+    }
+
+    /// Counts the zeros in `range`.
+    pub fn count_zeros(&self, range: Range<usize>) -> usize {
+        range.len() - self.count_ones(range)
+    }
+
+    /// Finds the position of the `rank`-th one in `range`, starting with 1.
+    pub fn select1_in(&self, range: Range<usize>, rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.count_ones(range.clone()) {
+            return None;
+        }
+        self.select1(self.rank1(range.start) + rank)
+    }
+
+    /// Finds the position of the `rank`-th zero in `range`, starting with 1.
+    pub fn select0_in(&self, range: Range<usize>, rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.count_zeros(range.clone()) {
+            return None;
+        }
+        self.select0(self.rank0(range.start) + rank)
+    }
+
+    /// Counts the occurrences of `pattern` which start in front of `idx`. `idx` may be the length
+    /// of the vector. The counters for the pattern have to be added first.
+    pub fn rank_pattern(&self, pattern: [bool; 2], idx: usize) -> usize {
+        assert!(idx <= self.len());
+        let index = self.pattern_index(pattern);
+        let idx = cmp::min(idx, self.pattern_limit());
+        if idx == self.pattern_limit() {
+            return index.count;
+        }
+        let l12 = &index.l12_indices[idx / L1_BIT_SIZE];
+        let l2_pos = (idx / L2_BIT_SIZE) % 4;
+        let l2: usize = (0..l2_pos).map(|i| l12.index(i) as usize).sum();
+        index.l0_indices[idx / L0_BIT_SIZE] as usize
+            + l12.l1() as usize
+            + l2
+            + self.count_pattern(pattern, idx - idx % L2_BIT_SIZE, idx)
+    }
+
+    /// Finds the first bit of the `rank`-th occurrence of `pattern`, starting with 1. The counters
+    /// for the pattern have to be added first.
+    pub fn select_pattern(&self, pattern: [bool; 2], mut rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        let index = self.pattern_index(pattern);
+        if rank > index.count {
+            return None;
+        }
+        let l0_pos = index.l0_indices.partition_point(|l0| (*l0 as usize) < rank) - 1;
+        rank -= index.l0_indices[l0_pos] as usize;
+
+        let first_l1 = l0_pos * L1_IN_L0_COUNT;
+        let last_l1 = cmp::min(first_l1 + L1_IN_L0_COUNT, index.l12_indices.len());
+        let l1_pos = first_l1
+            + index.l12_indices[first_l1..last_l1]
+                .partition_point(|l12| (l12.l1() as usize) < rank)
+            - 1;
+        let l12 = &index.l12_indices[l1_pos];
+        rank -= l12.l1() as usize;
+        let mut pos = l1_pos * L1_BIT_SIZE;
+        for i in 0..l12.len() {
+            if rank > l12.index(i) as usize {
+                rank -= l12.index(i) as usize;
+                pos += L2_BIT_SIZE;
+            } else {
+                break;
+            }
+        }
+
+        // Occurrences behind the limit come after all counted ones, so they are never reached.
+        let mut word = pos / 64;
+        loop {
+            let mut matches = self.pattern_matches(pattern, word);
+            let count = matches.count_ones() as usize;
+            if rank <= count {
+                for _ in 1..rank {
+                    matches &= matches - 1;
+                }
+                return Some(word * 64 + matches.trailing_zeros() as usize);
+            }
+            rank -= count;
+            word += 1;
+        }
+    }
+
     pub fn space_usage(&self) -> usize {
-        (self.l12_indices.len() * 64 + self.l0_indices.len() * 64) + mem::size_of::<Self>()
+        let patterns: usize = self
+            .patterns
+            .iter()
+            .map(|index| {
+                space_report::bits(&index.l12_indices) + space_report::bits(&index.l0_indices)
+            })
+            .sum();
+        self.index.counters_size() + patterns + mem::size_of::<Self>()
+    }
+
+    /// The space of the bits, the index and the counters of the patterns. The capacity of borrowed
+    /// bits belongs to their owner and is not reported.
+    pub fn space_report(&self) -> SpaceReport {
+        let bits = match &self.bits {
+            Bits::Vec(Cow::Borrowed(bit_vec)) => SpaceReport {
+                slack: 0,
+                ..space_report::bit_vec(bit_vec)
+            },
+            Bits::Vec(Cow::Owned(bit_vec)) => space_report::bit_vec(bit_vec),
+            Bits::Words(words, len) => SpaceReport {
+                data: *len,
+                padding: space_report::bits(words) - len,
+                slack: match words {
+                    Cow::Borrowed(_) => 0,
+                    Cow::Owned(words) => space_report::slack(words),
+                },
+                ..SpaceReport::default()
+            },
+            Bits::Aligned(words, len) => SpaceReport {
+                data: *len,
+                padding: words.line_bits() - len,
+                ..SpaceReport::default()
+            },
+        };
+        let patterns: SpaceReport = self
+            .patterns
+            .iter()
+            .map(|index| SpaceReport {
+                l0: space_report::bits(&index.l0_indices),
+                l12: space_report::bits(&index.l12_indices),
+                slack: space_report::slack(&index.l0_indices)
+                    + space_report::slack(&index.l12_indices),
+                ..SpaceReport::of::<PatternIndex>()
+            })
+            .sum();
+        SpaceReport {
+            slack: space_report::slack(&self.patterns),
+            ..SpaceReport::of::<Self>()
+        } + bits
+            + self.index.space_report().inline()
+            + patterns
+    }
+
+    fn pattern_index(&self, pattern: [bool; 2]) -> &PatternIndex {
+        self.patterns
+            .iter()
+            .find(|index| index.pattern == pattern)
+            .expect("no counters for the pattern")
+    }
+
+    // The number of positions at which a pattern can start.
+    fn pattern_limit(&self) -> usize {
+        self.len().saturating_sub(1)
+    }
+
+    // Marks each bit of the word at which `pattern` starts, including the bits behind the limit.
+    fn pattern_matches(&self, pattern: [bool; 2], word: usize) -> u64 {
+        let words = self.words();
+        let next = words.get(word + 1).map_or(0, |next| next & 1);
+        let first = words[word];
+        let second = (first >> 1) | (next << 63);
+        let first = if pattern[0] { first } else { !first };
+        let second = if pattern[1] { second } else { !second };
+        first & second
+    }
+
+    // Counts the occurrences of `pattern` which start in `start..end`.
+    fn count_pattern(&self, pattern: [bool; 2], start: usize, end: usize) -> usize {
+        let mut count = 0;
+        let mut pos = start;
+        while pos < end {
+            let offset = pos % 64;
+            let take = cmp::min(64 - offset, end - pos);
+            let mut matches = self.pattern_matches(pattern, pos / 64) >> offset;
+            if take < 64 {
+                matches &= (1 << take) - 1;
+            }
+            count += matches.count_ones() as usize;
+            pos += take;
+        }
+        count
+    }
+}
+
+/// The bits of a [`RunawayVector`] in a range, created by [`RunawayVector::range`]. Positions are
+/// relative to the whole vector.
+pub struct RangeView<'a> {
+    words: &'a [u64],
+    range: Range<usize>,
+}
+
+impl<'a> RangeView<'a> {
+    pub fn len(&self) -> usize {
+        self.range.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+
+    pub fn iter_ones(&self) -> Positions<'a> {
+        Positions::new(self.words, true, self.range.clone())
+    }
+
+    pub fn iter_zeros(&self) -> Positions<'a> {
+        Positions::new(self.words, false, self.range.clone())
+    }
+}
+
+/// An iterator over the positions of the ones or zeros in a range of a bit vector. Each step masks
+/// the word of the next position and takes its trailing or leading zeros, so words without a match
+/// are skipped at once.
+pub struct Positions<'a> {
+    words: &'a [u64],
+    bit: bool,
+    // The positions which are left to visit from both ends.
+    range: Range<usize>,
+}
+
+impl<'a> Positions<'a> {
+    fn new(words: &'a [u64], bit: bool, range: Range<usize>) -> Self {
+        Positions { words, bit, range }
+    }
+
+    // The matches in the word with index `word` which are inside the remaining range.
+    fn matches(&self, word: usize) -> u64 {
+        let mut matches = if self.bit {
+            self.words[word]
+        } else {
+            !self.words[word]
+        };
+        if self.range.start > word * 64 {
+            matches &= u64::MAX << (self.range.start - word * 64);
+        }
+        if self.range.end < (word + 1) * 64 {
+            matches &= (1 << (self.range.end - word * 64)) - 1;
+        }
+        matches
+    }
+}
+
+impl Iterator for Positions<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.range.is_empty() {
+            let word = self.range.start / 64;
+            let matches = self.matches(word);
+            if matches != 0 {
+                let pos = word * 64 + matches.trailing_zeros() as usize;
+                self.range.start = pos + 1;
+                return Some(pos);
+            }
+            self.range.start = (word + 1) * 64;
+        }
+        // Skipping a word can move the start behind the end.
+        self.range.start = self.range.end;
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.range.len()))
+    }
+}
+
+impl DoubleEndedIterator for Positions<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while !self.range.is_empty() {
+            let word = (self.range.end - 1) / 64;
+            let matches = self.matches(word);
+            if matches != 0 {
+                let pos = word * 64 + 63 - matches.leading_zeros() as usize;
+                self.range.end = pos;
+                return Some(pos);
+            }
+            self.range.end = word * 64;
+        }
+        self.range.end = self.range.start;
+        None
+    }
+}
+
+impl RankSelect for RunawayVector<'_> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn access(&self, idx: usize) -> bool {
+        self.access(idx)
+    }
+
+    fn rank0(&self, idx: usize) -> usize {
+        self.rank0(idx)
+    }
+
+    fn rank1(&self, idx: usize) -> usize {
+        self.rank1(idx)
+    }
+
+    fn select0(&self, rank: usize) -> Option<usize> {
+        self.select0(rank)
+    }
+
+    fn select1(&self, rank: usize) -> Option<usize> {
+        self.select1(rank)
+    }
+
+    fn count_ones(&self, range: Range<usize>) -> usize {
+        self.count_ones(range)
+    }
+
+    fn count_zeros(&self, range: Range<usize>) -> usize {
+        self.count_zeros(range)
+    }
+
+    fn select1_in(&self, range: Range<usize>, rank: usize) -> Option<usize> {
+        self.select1_in(range, rank)
+    }
+
+    fn select0_in(&self, range: Range<usize>, rank: usize) -> Option<usize> {
+        self.select0_in(range, rank)
+    }
+
+    fn next_one(&self, idx: usize) -> Option<usize> {
+        self.next_one(idx)
+    }
+
+    fn prev_one(&self, idx: usize) -> Option<usize> {
+        self.prev_one(idx)
+    }
+
+    fn next_zero(&self, idx: usize) -> Option<usize> {
+        self.next_zero(idx)
+    }
+
+    fn prev_zero(&self, idx: usize) -> Option<usize> {
+        self.prev_zero(idx)
+    }
+
+    fn space_usage(&self) -> usize {
+        self.space_usage()
+    }
+
+    fn space_report(&self) -> SpaceReport {
+        self.space_report()
     }
 }
 
@@ -324,14 +1481,14 @@ mod tests {
     fn small_vector() {
         let small = bitvec![u64, Lsb0; 1; 1024 + 256];
         let runaway = RunawayVector::new(&small);
-        assert_eq!(runaway.l0_indices.len(), 1);
-        assert_eq!(runaway.l0_indices[0], 0);
-        assert_eq!(runaway.l12_indices.len(), 1);
-        assert_eq!(runaway.l12_indices[0].l1(), 0);
-        assert_eq!(runaway.l12_indices[0].len(), 3);
-        assert_eq!(runaway.l12_indices[0].index(0), 512);
-        assert_eq!(runaway.l12_indices[0].index(1), 512);
-        assert_eq!(runaway.l12_indices[0].index(2), 256);
+        assert_eq!(runaway.index.l0_indices.len(), 1);
+        assert_eq!(runaway.index.l0_indices[0], 0);
+        assert_eq!(runaway.index.l12_indices.len(), 1);
+        assert_eq!(runaway.index.l12_indices[0].l1(), 0);
+        assert_eq!(runaway.index.l12_indices[0].len(), 3);
+        assert_eq!(runaway.index.l12_indices[0].index(0), 512);
+        assert_eq!(runaway.index.l12_indices[0].index(1), 512);
+        assert_eq!(runaway.index.l12_indices[0].index(2), 256);
     }
 
     #[test]
@@ -348,9 +1505,9 @@ mod tests {
         bv.set(L0_BIT_SIZE - 1, false);
         bv.extend(zeros.iter());
         let runaway = RunawayVector::new(&bv);
-        assert_eq!(runaway.l0_indices.len(), 2);
-        assert_eq!(runaway.l0_indices[0], 0);
-        assert_eq!(runaway.l0_indices[1], u32::MAX as u64);
+        assert_eq!(runaway.index.l0_indices.len(), 2);
+        assert_eq!(runaway.index.l0_indices[0], 0);
+        assert_eq!(runaway.index.l0_indices[1], u32::MAX as u64);
     }
 
     #[test]
@@ -360,9 +1517,9 @@ mod tests {
         bv.set(1 << 32, false);
         bv.set((1 << 32) * 2, false);
         let runaway = RunawayVector::new(&bv);
-        assert_eq!(runaway.l0_indices[0], 0);
-        assert_eq!(runaway.l0_indices[1], u32::MAX as u64);
-        assert_eq!(runaway.l0_indices[2], 2 * u32::MAX as u64);
+        assert_eq!(runaway.index.l0_indices[0], 0);
+        assert_eq!(runaway.index.l0_indices[1], u32::MAX as u64);
+        assert_eq!(runaway.index.l0_indices[2], 2 * u32::MAX as u64);
         assert_eq!(runaway.select0(1), Some(0));
         assert_eq!(runaway.select0(2), Some(1 << 32));
         assert_eq!(runaway.select0(3), Some((1 << 32) * 2));
@@ -388,9 +1545,9 @@ mod tests {
         bv[L1_BIT_SIZE..L1_BIT_SIZE + 64].store(u64::MIN);
         bv[L0_BIT_SIZE + L1_BIT_SIZE..L0_BIT_SIZE + L1_BIT_SIZE + 64].store(u64::MIN);
         let runaway = RunawayVector::new(&bv);
-        assert_eq!(runaway.l0_indices.len(), 2);
-        assert_eq!(runaway.l0_indices[0], 0);
-        assert_eq!(L0_BIT_SIZE as u64 - runaway.l0_indices[1], 64);
+        assert_eq!(runaway.index.l0_indices.len(), 2);
+        assert_eq!(runaway.index.l0_indices[0], 0);
+        assert_eq!(L0_BIT_SIZE as u64 - runaway.index.l0_indices[1], 64);
         assert_eq!(runaway.select0(64), Some(L1_BIT_SIZE + 63));
         assert_eq!(runaway.select0(128), Some(L0_BIT_SIZE + L1_BIT_SIZE + 63));
     }
@@ -402,9 +1559,9 @@ mod tests {
         bv.set(1 << 32, true);
         bv.set((1 << 32) * 2, true);
         let runaway = RunawayVector::new(&bv);
-        assert_eq!(runaway.l0_indices[0], 0);
-        assert_eq!(runaway.l0_indices[1], 1);
-        assert_eq!(runaway.l0_indices[2], 2);
+        assert_eq!(runaway.index.l0_indices[0], 0);
+        assert_eq!(runaway.index.l0_indices[1], 1);
+        assert_eq!(runaway.index.l0_indices[2], 2);
         assert_eq!(runaway.select1(1), Some(0));
         assert_eq!(runaway.select1(2), Some(1 << 32));
         assert_eq!(runaway.select1(3), Some((1 << 32) * 2));
@@ -418,8 +1575,8 @@ mod tests {
         bv[L1_BIT_SIZE * 2..L1_BIT_SIZE * 2 + 64].store(u64::MAX);
         bv[L1_BIT_SIZE * 3..L1_BIT_SIZE * 3 + 64].store(u64::MAX);
         let runaway = RunawayVector::new(&bv);
-        assert_eq!(runaway.l0_indices[0], 0);
-        assert_eq!(runaway.l12_indices.len(), 4);
+        assert_eq!(runaway.index.l0_indices[0], 0);
+        assert_eq!(runaway.index.l12_indices.len(), 4);
         assert_eq!(runaway.select1(1), Some(0));
         assert_eq!(runaway.select1(65), Some(L1_BIT_SIZE));
         assert_eq!(runaway.select1(65 + 64), Some(L1_BIT_SIZE * 2));
@@ -514,4 +1671,436 @@ mod tests {
             assert_eq!(runaway.rank1(i), i % L1_BIT_SIZE + 128 * 3);
         }
     }
+
+    #[test]
+    fn patterns() {
+        use rand::rngs::SmallRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = SmallRng::seed_from_u64(11);
+        for len in [0, 1, 2, 64, 65, 3000, 20_000] {
+            let bv: BitVec<u64, Lsb0> = (0..len).map(|_| rng.gen_bool(0.4)).collect();
+            let all = [[false, false], [false, true], [true, false], [true, true]];
+            let runaway = all
+                .iter()
+                .fold(RunawayVector::new(&bv), |runaway, pattern| {
+                    runaway.with_pattern(*pattern)
+                });
+            for pattern in all {
+                let mut rank = 0;
+                for i in 0..len {
+                    assert_eq!(runaway.rank_pattern(pattern, i), rank);
+                    if i + 1 < len && bv[i] == pattern[0] && bv[i + 1] == pattern[1] {
+                        rank += 1;
+                        assert_eq!(runaway.select_pattern(pattern, rank), Some(i));
+                    }
+                }
+                assert_eq!(runaway.rank_pattern(pattern, len), rank);
+                assert_eq!(runaway.select_pattern(pattern, rank + 1), None);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "no counters for the pattern")]
+    fn pattern_without_counters() {
+        let bv = bitvec![u64, Lsb0; 1; 100];
+        RunawayVector::new(&bv)
+            .with_pattern([true, true])
+            .rank_pattern([true, false], 10);
+    }
+
+    #[test]
+    fn next_and_prev() {
+        use rand::rngs::SmallRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = SmallRng::seed_from_u64(13);
+        for (len, density) in [
+            (1, 0.5),
+            (100, 0.5),
+            (5000, 0.001),
+            (5000, 0.999),
+            (5000, 0.5),
+        ] {
+            let bv: BitVec<u64, Lsb0> = (0..len).map(|_| rng.gen_bool(density)).collect();
+            let runaway = RunawayVector::new(&bv);
+            for i in 0..len {
+                for bit in [false, true] {
+                    let next = (i..len).find(|k| bv[*k] == bit);
+                    let prev = (0..=i).rev().find(|k| bv[*k] == bit);
+                    if bit {
+                        assert_eq!(runaway.next_one(i), next);
+                        assert_eq!(runaway.prev_one(i), prev);
+                    } else {
+                        assert_eq!(runaway.next_zero(i), next);
+                        assert_eq!(runaway.prev_zero(i), prev);
+                    }
+                }
+            }
+        }
+        // The unused bits of the last word must not be found.
+        let mut bv = bitvec![u64, Lsb0; 1; 70];
+        bv.truncate(66);
+        assert_eq!(RunawayVector::new(&bv).next_zero(3), None);
+    }
+
+    #[test]
+    fn iterators() {
+        use rand::rngs::SmallRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = SmallRng::seed_from_u64(17);
+        for (len, density) in [(0, 0.5), (63, 0.5), (5000, 0.01), (5000, 0.5), (5000, 0.99)] {
+            let bv: BitVec<u64, Lsb0> = (0..len).map(|_| rng.gen_bool(density)).collect();
+            let runaway = RunawayVector::new(&bv);
+            let ones: Vec<usize> = bv.iter_ones().collect();
+            let zeros: Vec<usize> = bv.iter_zeros().collect();
+            assert_eq!(runaway.iter_ones().collect::<Vec<usize>>(), ones);
+            assert_eq!(runaway.iter_zeros().collect::<Vec<usize>>(), zeros);
+            let mut reversed: Vec<usize> = runaway.iter_ones().rev().collect();
+            reversed.reverse();
+            assert_eq!(reversed, ones);
+            for _ in 0..50 {
+                let start = rng.gen_range(0..=len);
+                let end = rng.gen_range(start..=len);
+                let expected: Vec<usize> = (start..end).filter(|i| bv[*i]).collect();
+                let view = runaway.range(start..end);
+                assert_eq!(view.iter_ones().collect::<Vec<usize>>(), expected);
+                assert_eq!(view.iter_zeros().count(), end - start - expected.len());
+                let from: Vec<usize> = runaway.iter_ones_from(start).collect();
+                assert_eq!(from, ones[ones.partition_point(|one| *one < start)..]);
+            }
+        }
+    }
+
+    #[test]
+    fn iterate_from_both_ends() {
+        let mut bv = bitvec![u64, Lsb0; 0; 300];
+        for i in [0, 64, 65, 200, 299] {
+            bv.set(i, true);
+        }
+        let runaway = RunawayVector::new(&bv);
+        let mut ones = runaway.iter_ones();
+        assert_eq!(ones.next(), Some(0));
+        assert_eq!(ones.next_back(), Some(299));
+        assert_eq!(ones.next_back(), Some(200));
+        assert_eq!(ones.next(), Some(64));
+        assert_eq!(ones.next_back(), Some(65));
+        assert_eq!(ones.next(), None);
+        assert_eq!(ones.next_back(), None);
+    }
+
+    #[test]
+    fn range_queries() {
+        use rand::rngs::SmallRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = SmallRng::seed_from_u64(19);
+        let bv: BitVec<u64, Lsb0> = (0..10_000).map(|_| rng.gen_bool(0.3)).collect();
+        let runaway = RunawayVector::new(&bv);
+        for _ in 0..500 {
+            let start = rng.gen_range(0..=bv.len());
+            let end = rng.gen_range(start..=bv.len());
+            let ones: Vec<usize> = (start..end).filter(|i| bv[*i]).collect();
+            let zeros: Vec<usize> = (start..end).filter(|i| !bv[*i]).collect();
+            assert_eq!(runaway.count_ones(start..end), ones.len());
+            assert_eq!(runaway.count_zeros(start..end), zeros.len());
+            for rank in 1..=ones.len() + 1 {
+                assert_eq!(
+                    runaway.select1_in(start..end, rank),
+                    ones.get(rank - 1).copied()
+                );
+            }
+            for rank in (1..=zeros.len() + 1).step_by(3) {
+                assert_eq!(
+                    runaway.select0_in(start..end, rank),
+                    zeros.get(rank - 1).copied()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn shared_indices() {
+        use rand::rngs::SmallRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = SmallRng::seed_from_u64(47);
+        let bit_vec: BitVec<u64, Lsb0> = (0..5000).map(|_| rng.gen_bool(0.4)).collect();
+        let mask: BitVec<u64, Lsb0> = (0..5000).map(|_| rng.gen_bool(0.5)).collect();
+        let words = bit_vec.as_raw_slice();
+        let plain = RunawayIndex::new(words, 5000);
+        let complement = RunawayIndex::new(&Complement(words), 5000);
+        let masked_words = Masked::new(words, mask.as_raw_slice());
+        let masked = RunawayIndex::new(&masked_words, 5000);
+
+        let expected = RunawayVector::new(&bit_vec);
+        let anded = bit_vec.clone() & mask.clone();
+        let expected_masked = RunawayVector::new(&anded);
+        for i in 0..=5000 {
+            assert_eq!(plain.rank1(words, i), expected.rank1(i));
+            assert_eq!(complement.rank1(&Complement(words), i), expected.rank0(i));
+            assert_eq!(masked.rank1(&masked_words, i), expected_masked.rank1(i));
+        }
+        for rank in 1..=complement.ones() {
+            assert_eq!(
+                complement.select1(&Complement(words), rank),
+                expected.select0(rank)
+            );
+        }
+        for rank in 1..=masked.ones() {
+            assert_eq!(
+                masked.select1(&masked_words, rank),
+                expected_masked.select1(rank)
+            );
+        }
+
+        let vector = RunawayVector::with_index(&bit_vec, plain);
+        assert_eq!(vector.rank1(4321), expected.rank1(4321));
+        assert_eq!(vector.select0(100), expected.select0(100));
+    }
+
+    #[test]
+    #[should_panic(expected = "index was built for a different length")]
+    fn with_index_of_other_length() {
+        let bit_vec: BitVec<u64, Lsb0> = BitVec::repeat(true, 100);
+        let index = RunawayIndex::new(bit_vec.as_raw_slice(), 90);
+        RunawayVector::with_index(&bit_vec, index);
+    }
+
+    #[test]
+    fn verify() {
+        let bit_vec: BitVec<u64, Lsb0> = (0..20_000).map(|i| i % 7 == 0 || i % 11 == 0).collect();
+        let runaway = RunawayVector::new(&bit_vec);
+        assert_eq!(runaway.verify(), Ok(()));
+        assert_eq!(runaway.verify_quick(), Ok(()));
+        let checksum = runaway.checksum();
+        let words = bit_vec.as_raw_slice();
+
+        let mut index = RunawayIndex::new(words, 20_000);
+        let l1 = index.l12_indices[5].l1();
+        let l2s: Vec<u16> = (0..3).map(|k| index.l12_indices[5].index(k)).collect();
+        index.l12_indices[5] = InterleavedIndex::new(l1 + 1, &l2s);
+        let mismatch = IndexMismatch::L1 {
+            idx: 5,
+            expected: l1,
+            found: l1 + 1,
+        };
+        assert_eq!(index.verify(words), Err(mismatch.clone()));
+        // The sampled blocks are checked against the block behind them.
+        assert_eq!(index.verify_quick(words), Err(mismatch));
+        assert_ne!(index.checksum(), checksum);
+
+        let mut index = RunawayIndex::new(words, 20_000);
+        index.l12_indices[9] = InterleavedIndex::new(index.l12_indices[9].l1(), &[0, 1, 2]);
+        assert!(matches!(
+            index.verify(words),
+            Err(IndexMismatch::L2 {
+                idx: 36,
+                found: 0,
+                ..
+            })
+        ));
+        assert!(matches!(
+            index.verify_quick(words),
+            Err(IndexMismatch::L2 {
+                idx: 36,
+                found: 0,
+                ..
+            })
+        ));
+
+        let mut index = RunawayIndex::new(words, 20_000);
+        index.ones += 1;
+        assert!(matches!(
+            index.verify(words),
+            Err(IndexMismatch::Ones { .. })
+        ));
+        assert!(matches!(
+            index.verify_quick(words),
+            Err(IndexMismatch::Ones { .. })
+        ));
+        index.l12_indices = Counters::Vec(index.l12_indices[..index.l1_entry_count() - 1].to_vec());
+        assert_eq!(index.verify(words), Err(IndexMismatch::Entries));
+        assert_eq!(RunawayIndex::new(words, 20_000).checksum(), checksum);
+
+        for len in [0, 1, 512, 1600, 2048, 4096 + 1537, 6000] {
+            let bit_vec: BitVec<u64, Lsb0> = (0..len).map(|i| i % 3 == 0).collect();
+            let runaway = RunawayVector::new(&bit_vec);
+            assert_eq!(runaway.verify(), Ok(()));
+            assert_eq!(runaway.verify_quick(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn introspection() {
+        let bit_vec: BitVec<u64, Lsb0> = (0..5000).map(|i| i % 4 == 0).collect();
+        let runaway = RunawayVector::new(&bit_vec);
+        let index = runaway.index();
+        assert_eq!(index.l0_entries(), &[0]);
+        assert_eq!(index.l1_entry_count(), 3);
+        assert_eq!(index.l1_entry(1), 512);
+        assert_eq!(index.l2_counts(1), vec![128, 128, 128]);
+        assert_eq!(index.l2_counts(2), vec![128, 98]);
+        assert_eq!(
+            format!("{:?}", index.l12_indices[2]),
+            "InterleavedIndex { l1: 1024, l2: [128, 98] }"
+        );
+
+        let block = runaway.l1_block(2);
+        assert_eq!(block.bits, 4096..5000);
+        assert_eq!(block.l1, 1024);
+        assert_eq!(block.counted, vec![128, 98]);
+        assert!(format!("{:#?}", block).contains("counted: [\n"));
+    }
+
+    // A vector cut from another one, whose bits start at bit 5 of their first word.
+    fn unaligned() -> (BitVec<u64, Lsb0>, BitVec<u64, Lsb0>) {
+        let base: BitVec<u64, Lsb0> = (0..5005).map(|i| (i * 7 + i / 300) % 3 == 0).collect();
+        let bit_vec = base[5..].to_bitvec();
+        assert!(!word::is_aligned(&bit_vec));
+        let expected: BitVec<u64, Lsb0> = bit_vec.iter().by_vals().collect();
+        (bit_vec, expected)
+    }
+
+    #[test]
+    fn unaligned_rank() {
+        let (bit_vec, expected) = unaligned();
+        for runaway in [
+            RunawayVector::new(&bit_vec),
+            RunawayVector::from_owned(bit_vec.clone()),
+            RunawayVector::with_pages(&bit_vec, Pages::Huge),
+        ] {
+            for i in 0..=expected.len() {
+                assert_eq!(runaway.rank1(i), expected[..i].count_ones());
+            }
+            for i in 0..expected.len() {
+                assert_eq!(runaway.access(i), expected[i]);
+            }
+            assert_eq!(runaway.verify(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn pages() {
+        for pages in [Pages::Normal, Pages::Huge] {
+            for len in [0, 1, 512, 2048 * 3 + 100, 20_000] {
+                let bit_vec: BitVec<u64, Lsb0> = (0..len).map(|i| i % 5 < 2).collect();
+                let expected = RunawayVector::new(&bit_vec);
+                let aligned = RunawayVector::with_pages(&bit_vec, pages);
+                assert_eq!(aligned.words().as_ptr() as usize % 64, 0);
+                assert_eq!(aligned.index.l12_indices.as_ptr() as usize % 64, 0);
+                assert_eq!(aligned.checksum(), expected.checksum());
+                assert_eq!(aligned.verify(), Ok(()));
+                for i in (0..=len).step_by(37) {
+                    assert_eq!(aligned.rank1(i), expected.rank1(i));
+                }
+                for rank in (1..=expected.rank1(len)).step_by(31) {
+                    assert_eq!(aligned.select1(rank), expected.select1(rank));
+                }
+                let report = aligned.space_report();
+                assert_eq!(report.data, len);
+                // The bits and the L1/L2 counters fill whole cache lines.
+                assert_eq!((report.data + report.l12 + report.padding) % 512, 0);
+            }
+        }
+        let bit_vec: BitVec<u64, Lsb0> = BitVec::repeat(true, 100);
+        assert!(!RunawayVector::with_pages(&bit_vec, Pages::Normal).is_mapped());
+        assert!(!RunawayVector::new(&bit_vec).is_mapped());
+    }
+
+    #[test]
+    fn unaligned_select() {
+        let (bit_vec, expected) = unaligned();
+        let runaway = RunawayVector::new(&bit_vec);
+        assert_eq!(runaway.select1(1), expected.first_one());
+        for (rank, idx) in expected.iter_ones().enumerate() {
+            assert_eq!(runaway.select1(rank + 1), Some(idx));
+        }
+        for (rank, idx) in expected.iter_zeros().enumerate() {
+            assert_eq!(runaway.select0(rank + 1), Some(idx));
+        }
+    }
+
+    #[test]
+    fn unaligned_next_prev() {
+        let (bit_vec, expected) = unaligned();
+        let runaway = RunawayVector::new(&bit_vec);
+        assert_eq!(
+            runaway.next_one(2),
+            expected[2..].first_one().map(|i| i + 2)
+        );
+        for i in (0..expected.len()).step_by(7) {
+            assert_eq!(
+                runaway.next_one(i),
+                expected[i..].first_one().map(|j| i + j)
+            );
+            assert_eq!(
+                runaway.next_zero(i),
+                expected[i..].first_zero().map(|j| i + j)
+            );
+            assert_eq!(runaway.prev_one(i), expected[..=i].last_one());
+            assert_eq!(runaway.prev_zero(i), expected[..=i].last_zero());
+        }
+    }
+
+    #[test]
+    fn unaligned_positions() {
+        let (bit_vec, expected) = unaligned();
+        let runaway = RunawayVector::new(&bit_vec);
+        assert_eq!(
+            runaway.iter_ones().take(3).collect::<Vec<_>>(),
+            vec![1, 4, 7]
+        );
+        assert!(runaway.iter_ones().eq(expected.iter_ones()));
+        assert!(runaway.iter_zeros().rev().eq(expected.iter_zeros().rev()));
+        assert!(runaway
+            .range(100..900)
+            .iter_ones()
+            .eq(expected[100..900].iter_ones().map(|i| i + 100)));
+    }
+
+    #[test]
+    #[should_panic(expected = "bits do not start at the first bit of a word")]
+    fn unaligned_with_index() {
+        let (bit_vec, expected) = unaligned();
+        let index = RunawayIndex::new(expected.as_raw_slice(), expected.len());
+        RunawayVector::with_index(&bit_vec, index);
+    }
+
+    #[test]
+    fn space_report() {
+        let bit_vec: BitVec<u64, Lsb0> = (0..5000).map(|i| i % 3 == 0).collect();
+        let runaway = RunawayVector::new(&bit_vec);
+        let index = runaway.index().space_report();
+        assert_eq!(index.l0, 64 * runaway.index().l0_entries().len());
+        assert_eq!(index.l12, 128 * runaway.index().l1_entry_count());
+        assert_eq!(index.overhead, 8 * mem::size_of::<RunawayIndex>());
+        // The single number counts the same counters as the report.
+        assert_eq!(
+            runaway.index().space_usage() - mem::size_of::<RunawayIndex>(),
+            index.l0 + index.l12
+        );
+        let patterned = RunawayVector::new(&bit_vec).with_pattern([true, false]);
+        assert_eq!(
+            patterned.space_usage() - patterned.index().space_usage(),
+            index.l0 + index.l12 + mem::size_of::<RunawayVector>() - mem::size_of::<RunawayIndex>()
+        );
+
+        let report = runaway.space_report();
+        assert_eq!(report.data, 5000);
+        assert_eq!(report.padding, 79 * 64 - 5000);
+        assert_eq!((report.l0, report.l12), (index.l0, index.l12));
+        assert_eq!(report.overhead, 8 * mem::size_of::<RunawayVector>());
+        assert_eq!(report.slack, index.slack);
+        assert_eq!(report.total() - report.support(), 5000);
+
+        // Owned bits report their capacity, borrowed bits belong to the caller.
+        let mut owned = bit_vec.clone();
+        owned.reserve(10_000);
+        let capacity = owned.capacity();
+        let owned = RunawayVector::from_owned(owned);
+        assert_eq!(owned.space_report().slack, index.slack + capacity - 79 * 64);
+    }
 }
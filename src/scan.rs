@@ -0,0 +1,221 @@
+//! # Scan
+//!
+//! The last steps of a select on a [`crate::runaway_vector::RunawayVector`]: finding the L2 block
+//! inside a L1 block and the word inside the L2 block which hold the searched bit.
+//!
+//! The up to three L2 counts and the eight words of a L2 block are counted, summed up and compared
+//! with the rank with AVX2 when the CPU supports it, which is detected at runtime. Otherwise the
+//! L2 counts are compared without branches and the words are counted one after another, which
+//! gives the same results.
+//!
+use crate::runaway_vector::{InterleavedIndex, L2_BIT_SIZE};
+
+/// The L2 block of `index` which holds the `rank`-th one or zero of the L1 block, together with
+/// the rank inside that L2 block.
+pub(crate) fn l2_block(index: &InterleavedIndex, rank: usize, bit: bool) -> (usize, usize) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: the CPU supports AVX2.
+        return unsafe { avx2::l2_block(index, rank, bit) };
+    }
+    l2_block_scalar(index, rank, bit)
+}
+
+fn l2_block_scalar(index: &InterleavedIndex, rank: usize, bit: bool) -> (usize, usize) {
+    let mut prefix = 0;
+    let mut block = 0;
+    let mut before = 0;
+    for i in 0..index.len() {
+        prefix += match bit {
+            true => index.index(i) as usize,
+            false => L2_BIT_SIZE - index.index(i) as usize,
+        };
+        // The prefix sums only grow, so the blocks in front of the searched one are a prefix.
+        let passed = prefix < rank;
+        block += passed as usize;
+        before = if passed { prefix } else { before };
+    }
+    (block, rank - before)
+}
+
+/// The word of `words` which holds the `rank`-th one, together with the rank inside that word, or
+/// `None` if there are fewer ones.
+pub(crate) fn word_in_block(words: &[u64; 8], rank: usize) -> Option<(usize, usize)> {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: the CPU supports AVX2.
+        return unsafe { avx2::word_in_block(words, rank) };
+    }
+    word_in_block_scalar(words, rank)
+}
+
+fn word_in_block_scalar(words: &[u64; 8], mut rank: usize) -> Option<(usize, usize)> {
+    for (i, word) in words.iter().enumerate() {
+        let ones = word.count_ones() as usize;
+        if rank <= ones {
+            return Some((i, rank));
+        }
+        rank -= ones;
+    }
+    None
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use crate::runaway_vector::{InterleavedIndex, L2_BIT_SIZE, L2_INDEX_BIT_SIZE};
+
+    #[target_feature(enable = "avx2")]
+    pub(super) fn l2_block(index: &InterleavedIndex, rank: usize, bit: bool) -> (usize, usize) {
+        // The counts are shifted into the lanes 0 to 2, and the lanes behind the stored counts
+        // are cleared.
+        let shifts = _mm_setr_epi32(0, 10, 20, 30);
+        let counts = _mm_and_si128(
+            _mm_srlv_epi32(_mm_set1_epi32(index.packed_l2s() as i32), shifts),
+            _mm_set1_epi32((1 << L2_INDEX_BIT_SIZE) - 1),
+        );
+        let counts = match bit {
+            true => counts,
+            false => _mm_sub_epi32(_mm_set1_epi32(L2_BIT_SIZE as i32), counts),
+        };
+        let valid = _mm_cmpgt_epi32(
+            _mm_set1_epi32(index.len() as i32),
+            _mm_setr_epi32(0, 1, 2, 3),
+        );
+        let counts = _mm_and_si128(counts, valid);
+        let sums = _mm_add_epi32(counts, _mm_slli_si128::<4>(counts));
+        let sums = _mm_add_epi32(sums, _mm_slli_si128::<8>(sums));
+        // The sums stay below 2048, so larger ranks compare the same when they are capped.
+        let rank_lanes = _mm_set1_epi32(rank.min(1 << 20) as i32);
+        let passed = _mm_and_si128(_mm_cmpgt_epi32(rank_lanes, sums), valid);
+        let block = _mm_movemask_ps(_mm_castsi128_ps(passed)).count_ones() as usize;
+        let mut prefix = [0u32; 4];
+        // Safety: `prefix` holds four 32 bit lanes.
+        unsafe { _mm_storeu_si128(prefix.as_mut_ptr() as *mut __m128i, sums) };
+        let before = match block {
+            0 => 0,
+            _ => prefix[block - 1] as usize,
+        };
+        (block, rank - before)
+    }
+
+    // The number of ones in each of the four words of `words`.
+    #[target_feature(enable = "avx2")]
+    fn popcounts(words: __m256i) -> __m256i {
+        let table = _mm256_setr_epi8(
+            0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2, 3, 3, 4, 0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2,
+            3, 3, 4,
+        );
+        let nibbles = _mm256_set1_epi8(0x0F);
+        let low = _mm256_shuffle_epi8(table, _mm256_and_si256(words, nibbles));
+        let high = _mm256_shuffle_epi8(
+            table,
+            _mm256_and_si256(_mm256_srli_epi16::<4>(words), nibbles),
+        );
+        _mm256_sad_epu8(_mm256_add_epi8(low, high), _mm256_setzero_si256())
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) fn word_in_block(words: &[u64; 8], rank: usize) -> Option<(usize, usize)> {
+        if rank > 8 * 64 {
+            return None;
+        }
+        // Safety: both loads read four words inside `words`.
+        let (first, second) = unsafe {
+            let ptr = words.as_ptr() as *const __m256i;
+            (_mm256_loadu_si256(ptr), _mm256_loadu_si256(ptr.add(1)))
+        };
+        // The counts of the words 0 to 3 and 4 to 7 are interleaved into 32 bit lanes and then
+        // brought into the order of the words.
+        let counts = _mm256_or_si256(popcounts(first), _mm256_slli_epi64::<32>(popcounts(second)));
+        let counts = _mm256_permutevar8x32_epi32(counts, _mm256_setr_epi32(0, 2, 4, 6, 1, 3, 5, 7));
+        // The prefix sums inside each 128 bit lane, to which the upper lane adds the sum of the
+        // lower lane.
+        let sums = _mm256_add_epi32(counts, _mm256_slli_si256::<4>(counts));
+        let sums = _mm256_add_epi32(sums, _mm256_slli_si256::<8>(sums));
+        let carry = _mm256_permutevar8x32_epi32(sums, _mm256_set1_epi32(3));
+        let sums = _mm256_add_epi32(
+            sums,
+            _mm256_blend_epi32::<0b1111_0000>(_mm256_setzero_si256(), carry),
+        );
+        // The words with fewer ones in front of and in them than the rank come before the
+        // searched word.
+        let smaller = _mm256_cmpgt_epi32(_mm256_set1_epi32(rank as i32), sums);
+        let word = _mm256_movemask_ps(_mm256_castsi256_ps(smaller)).count_ones() as usize;
+        if word == 8 {
+            return None;
+        }
+        let mut prefix = [0u32; 8];
+        // Safety: `prefix` holds eight 32 bit lanes.
+        unsafe { _mm256_storeu_si256(prefix.as_mut_ptr() as *mut __m256i, sums) };
+        let before = match word {
+            0 => 0,
+            _ => prefix[word - 1] as usize,
+        };
+        Some((word, rank - before))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn word_in_block() {
+        let mut rng = SmallRng::seed_from_u64(41);
+        for _ in 0..1000 {
+            let mut words = [0u64; 8];
+            for word in words.iter_mut() {
+                *word = match rng.gen_range(0..4) {
+                    0 => 0,
+                    1 => u64::MAX,
+                    _ => rng.gen(),
+                };
+            }
+            let ones: usize = words.iter().map(|word| word.count_ones() as usize).sum();
+            for rank in 1..=ones + 1 {
+                let expected = word_in_block_scalar(&words, rank);
+                assert_eq!(super::word_in_block(&words, rank), expected);
+            }
+            assert_eq!(word_in_block_scalar(&words, ones + 1), None);
+        }
+    }
+
+    #[test]
+    fn l2_block_simd() {
+        let mut rng = SmallRng::seed_from_u64(43);
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..4);
+            let l2s: Vec<u16> = (0..len)
+                .map(|_| match rng.gen_range(0..4) {
+                    0 => 0,
+                    1 => 512,
+                    _ => rng.gen_range(0..=512),
+                })
+                .collect();
+            let index = InterleavedIndex::new(rng.gen(), &l2s);
+            for bit in [true, false] {
+                for rank in 0..=len * 512 + 1 {
+                    let expected = l2_block_scalar(&index, rank, bit);
+                    assert_eq!(super::l2_block(&index, rank, bit), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn l2_block() {
+        let index = InterleavedIndex::new(0, &[100, 5, 512]);
+        assert_eq!(super::l2_block(&index, 1, true), (0, 1));
+        assert_eq!(super::l2_block(&index, 100, true), (0, 100));
+        assert_eq!(super::l2_block(&index, 101, true), (1, 1));
+        assert_eq!(super::l2_block(&index, 106, true), (2, 1));
+        assert_eq!(super::l2_block(&index, 618, true), (3, 1));
+        assert_eq!(super::l2_block(&index, 412, false), (0, 412));
+        assert_eq!(super::l2_block(&index, 413, false), (1, 1));
+        assert_eq!(super::l2_block(&index, 920, false), (3, 1));
+    }
+}
@@ -0,0 +1,241 @@
+//! # Serialization
+//!
+//! Helpers for writing the structures of this crate to disk. Everything is stored as little endian
+//! `u64` words. Bit vectors are stored as their length in bits followed by their raw words, so a
+//! file can be read back without knowing anything about the structure which wrote it.
+//!
+//! Each field starts at a multiple of 8 bytes, so the words of a file which is mapped with [`map`]
+//! can be borrowed in place with [`borrow_bit_vec`] instead of being copied.
+//!
+use std::borrow::Cow;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use bitvec::field::BitField;
+use bitvec::order::Lsb0;
+use bitvec::prelude::{BitSlice, BitVec};
+use memmap2::Mmap;
+
+pub fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Reads a `u64` which is used as a length or position in memory.
+pub fn read_usize<R: Read>(reader: &mut R) -> io::Result<usize> {
+    usize::try_from(read_u64(reader)?).map_err(|_| invalid_data("value does not fit into usize"))
+}
+
+/// Writes the length of `values` followed by the values.
+pub fn write_u64s<W: Write>(writer: &mut W, values: &[u64]) -> io::Result<()> {
+    write_u64(writer, values.len() as u64)?;
+    for value in values {
+        write_u64(writer, *value)?;
+    }
+    Ok(())
+}
+
+pub fn read_u64s<R: Read>(reader: &mut R) -> io::Result<Vec<u64>> {
+    let len = read_usize(reader)?;
+    (0..len).map(|_| read_u64(reader)).collect()
+}
+
+/// Writes the length of `bytes` followed by the bytes, padded with zeros to a multiple of 8.
+pub fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_u64(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)?;
+    writer.write_all(&[0; 8][..padding(bytes.len())])
+}
+
+/// Reads bytes written by [`write_bytes`]. The length is not trusted, so the bytes are only
+/// allocated as far as the reader actually holds them.
+pub fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = read_usize(reader)?;
+    let padded = len
+        .checked_add(padding(len))
+        .ok_or_else(|| invalid_data("length does not fit into usize"))?;
+    let mut bytes = Vec::new();
+    reader
+        .by_ref()
+        .take(padded as u64)
+        .read_to_end(&mut bytes)?;
+    if bytes.len() != padded {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "bytes end early",
+        ));
+    }
+    bytes.truncate(len);
+    Ok(bytes)
+}
+
+/// Borrows bytes written by [`write_bytes`] from the front of `bytes` and moves past them.
+pub fn borrow_bytes<'a>(bytes: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let len = read_usize(bytes)?;
+    let padded = len
+        .checked_add(padding(len))
+        .ok_or_else(|| invalid_data("length does not fit into usize"))?;
+    Ok(&split(bytes, padded)?[..len])
+}
+
+/// Writes the length of the bits followed by their words. The words are loaded from the bits, so
+/// bits which do not start at the lowest bit of a word, like a slice of a vector, are written in
+/// the same layout as a vector of their own, and the unused bits of the last word are zero.
+pub fn write_bit_vec<W: Write>(writer: &mut W, bits: &BitSlice<u64, Lsb0>) -> io::Result<()> {
+    write_u64(writer, bits.len() as u64)?;
+    for chunk in bits.chunks(64) {
+        write_u64(writer, chunk.load_le::<u64>())?;
+    }
+    Ok(())
+}
+
+pub fn read_bit_vec<R: Read>(reader: &mut R) -> io::Result<BitVec<u64, Lsb0>> {
+    let len = read_usize(reader)?;
+    let words = (0..len.div_ceil(64))
+        .map(|_| read_u64(reader))
+        .collect::<io::Result<Vec<u64>>>()?;
+    let mut bit_vec = BitVec::from_vec(words);
+    bit_vec.truncate(len);
+    Ok(bit_vec)
+}
+
+/// Borrows the words of a bit vector written by [`write_bit_vec`] from the front of `bytes` and
+/// moves past them. Returns the words and the number of bits. The words are only copied if they
+/// do not start at a multiple of 8 bytes in memory or if the target is not little endian.
+pub fn borrow_bit_vec<'a>(bytes: &mut &'a [u8]) -> io::Result<(Cow<'a, [u64]>, usize)> {
+    let len = read_usize(bytes)?;
+    let words = split(bytes, len.div_ceil(64) * 8)?;
+    // Safety: Every bit pattern is a valid u64.
+    let (head, aligned, _) = unsafe { words.align_to::<u64>() };
+    if head.is_empty() && cfg!(target_endian = "little") {
+        return Ok((Cow::Borrowed(aligned), len));
+    }
+    let words = words
+        .chunks_exact(8)
+        .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    Ok((Cow::Owned(words), len))
+}
+
+/// Maps a file into memory, so that structures can borrow their words from it, like
+/// [`crate::trie::Trie::from_bytes`].
+///
+/// # Safety
+///
+/// The file must not be modified or truncated while it is mapped, as the borrowed words are read
+/// without checking them again.
+pub unsafe fn map<P: AsRef<Path>>(path: P) -> io::Result<Mmap> {
+    let file = File::open(path)?;
+    // Safety: The caller guarantees that the file is not changed while it is mapped.
+    unsafe { Mmap::map(&file) }
+}
+
+// Splits `len` bytes off the front of `bytes`.
+fn split<'a>(bytes: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "bytes end early",
+        ));
+    }
+    let (front, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(front)
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn padding(len: usize) -> usize {
+    (8 - len % 8) % 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitvec::bitvec;
+
+    #[test]
+    fn bit_vec_round_trip() {
+        let mut bv = bitvec![u64, Lsb0; 0; 130];
+        bv.set(0, true);
+        bv.set(64, true);
+        bv.set(129, true);
+        let mut buffer = Vec::new();
+        write_bit_vec(&mut buffer, &bv).unwrap();
+        assert_eq!(buffer.len(), 8 * 4);
+        assert_eq!(read_bit_vec(&mut buffer.as_slice()).unwrap(), bv);
+    }
+
+    #[test]
+    fn unaligned_bit_vec() {
+        let bv: BitVec<u64, Lsb0> = (0..200).map(|i| i % 3 == 0).collect();
+        let mut buffer = Vec::new();
+        write_bit_vec(&mut buffer, &bv[5..]).unwrap();
+        let read = read_bit_vec(&mut buffer.as_slice()).unwrap();
+        assert_eq!(read, bv[5..]);
+        assert_eq!(read.as_raw_slice()[0] & 1, 0);
+        assert_eq!(read.as_raw_slice()[0] & 2, 2);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut buffer = Vec::new();
+        write_bytes(&mut buffer, b"banana").unwrap();
+        write_u64s(&mut buffer, &[1, 2, 3]).unwrap();
+        assert_eq!(buffer.len() % 8, 0);
+        let mut reader = buffer.as_slice();
+        assert_eq!(read_bytes(&mut reader).unwrap(), b"banana");
+        assert_eq!(read_u64s(&mut reader).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn borrowed() {
+        let bv: BitVec<u64, Lsb0> = (0..130).map(|i| i % 5 == 0).collect();
+        let mut buffer = Vec::new();
+        write_bit_vec(&mut buffer, &bv).unwrap();
+        write_bytes(&mut buffer, b"banana").unwrap();
+        // Words keep their alignment in memory and are borrowed, the others are copied.
+        let words: Vec<u64> = buffer
+            .chunks(8)
+            .map(|word| u64::from_ne_bytes(word.try_into().unwrap()))
+            .collect();
+        let mut shifted = vec![0];
+        shifted.extend_from_slice(&buffer);
+        for (mut bytes, borrowed) in [
+            (as_bytes(&words), cfg!(target_endian = "little")),
+            (&shifted[1..], false),
+        ] {
+            let (read, len) = borrow_bit_vec(&mut bytes).unwrap();
+            assert_eq!(matches!(read, Cow::Borrowed(_)), borrowed);
+            assert_eq!(BitSlice::<u64, Lsb0>::from_slice(&read)[..len], bv);
+            assert_eq!(borrow_bytes(&mut bytes).unwrap(), b"banana");
+            assert!(bytes.is_empty());
+            assert!(borrow_bit_vec(&mut bytes).is_err());
+        }
+    }
+
+    // The bytes of `words` in memory.
+    fn as_bytes(words: &[u64]) -> &[u8] {
+        // Safety: A u8 has no alignment and every byte of a u64 is initialized.
+        unsafe { std::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 8) }
+    }
+
+    #[test]
+    fn bytes_untrusted_length() {
+        for len in [u64::MAX - 3, 1 << 40, 9] {
+            let mut buffer = Vec::new();
+            write_u64(&mut buffer, len).unwrap();
+            buffer.extend_from_slice(b"banana\0\0");
+            assert!(read_bytes(&mut buffer.as_slice()).is_err());
+        }
+    }
+}
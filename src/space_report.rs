@@ -0,0 +1,221 @@
+//! # SpaceReport
+//!
+//! The space of a structure split into its parts, all counted in bits. Unlike `space_usage`, which
+//! sums everything into a single number, a report counts the memory as it is allocated: the size
+//! of the struct itself, the padding of the last word or cache line and the unused capacity of
+//! its vectors are reported apart from the data and the counters.
+//!
+//! Reports of the parts of a structure are added up with `+`. A part which is stored inline is
+//! added with [`SpaceReport::inline`], as its struct is already counted by the outer one.
+//!
+use std::fmt;
+use std::iter::Sum;
+use std::mem;
+use std::ops::{Add, AddAssign};
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpaceReport {
+    /// The bits of the data, which is the plain bit vector for vectors which only index it and the
+    /// encoding for compressed structures.
+    pub data: usize,
+    /// The counters of the top level of a rank directory, which count the ones in front of large
+    /// blocks or in front of each block for single level directories.
+    pub l0: usize,
+    /// The counters of the lower levels of a rank directory.
+    pub l12: usize,
+    /// The sampled positions which speed up select.
+    pub select_samples: usize,
+    /// Any other support structure, like the keys of a roaring bitmap or the excess of a tree.
+    pub other: usize,
+    /// The unused bits behind the data in its last word or cache line.
+    pub padding: usize,
+    /// The size of the structs themselves.
+    pub overhead: usize,
+    /// The unused capacity of the vectors.
+    pub slack: usize,
+}
+
+impl SpaceReport {
+    /// A report which only holds the size of the struct `T`.
+    pub fn of<T>() -> Self {
+        SpaceReport {
+            overhead: mem::size_of::<T>() * 8,
+            ..Self::default()
+        }
+    }
+
+    /// The report of a part which is stored inside another struct, without the size of its struct.
+    pub fn inline(self) -> Self {
+        SpaceReport {
+            overhead: 0,
+            ..self
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.data + self.support()
+    }
+
+    /// Everything but the data.
+    pub fn support(&self) -> usize {
+        self.l0
+            + self.l12
+            + self.select_samples
+            + self.other
+            + self.padding
+            + self.overhead
+            + self.slack
+    }
+
+    fn rows(&self) -> [(&'static str, usize); 8] {
+        [
+            ("data", self.data),
+            ("l0", self.l0),
+            ("l12", self.l12),
+            ("select_samples", self.select_samples),
+            ("other", self.other),
+            ("padding", self.padding),
+            ("overhead", self.overhead),
+            ("slack", self.slack),
+        ]
+    }
+
+    /// The parts and the total as a JSON object on a single line.
+    pub fn to_json(&self) -> String {
+        let fields: Vec<String> = self
+            .rows()
+            .iter()
+            .chain([("total", self.total())].iter())
+            .map(|(name, bits)| format!("\"{name}\":{bits}"))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+/// A table with the bits of each part and its share of the total.
+impl fmt::Display for SpaceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        let share = |bits: usize| match total {
+            0 => 0.0,
+            _ => bits as f64 * 100.0 / total as f64,
+        };
+        writeln!(f, "{:<16}{:>16}{:>9}", "part", "bits", "share")?;
+        for (name, bits) in self.rows() {
+            writeln!(f, "{:<16}{:>16}{:>8.2}%", name, bits, share(bits))?;
+        }
+        writeln!(f, "{:<16}{:>16}{:>8.2}%", "total", total, share(total))
+    }
+}
+
+impl Add for SpaceReport {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self += other;
+        self
+    }
+}
+
+impl AddAssign for SpaceReport {
+    fn add_assign(&mut self, other: Self) {
+        self.data += other.data;
+        self.l0 += other.l0;
+        self.l12 += other.l12;
+        self.select_samples += other.select_samples;
+        self.other += other.other;
+        self.padding += other.padding;
+        self.overhead += other.overhead;
+        self.slack += other.slack;
+    }
+}
+
+impl Sum for SpaceReport {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+/// The bits of the elements of `values`.
+pub(crate) fn bits<T>(values: &[T]) -> usize {
+    mem::size_of_val(values) * 8
+}
+
+/// The bits of the unused capacity of `vec`.
+pub(crate) fn slack<T>(vec: &Vec<T>) -> usize {
+    (vec.capacity() - vec.len()) * mem::size_of::<T>() * 8
+}
+
+/// The report of a bit vector which is stored as data.
+pub(crate) fn bit_vec(bit_vec: &BitVec<u64, Lsb0>) -> SpaceReport {
+    let words = bit_vec.as_raw_slice().len() * 64;
+    SpaceReport {
+        data: bit_vec.len(),
+        padding: words - bit_vec.len(),
+        slack: bit_vec.capacity().saturating_sub(words),
+        ..SpaceReport::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums() {
+        let report = SpaceReport {
+            data: 1000,
+            l0: 64,
+            l12: 128,
+            padding: 24,
+            ..SpaceReport::of::<u64>()
+        };
+        assert_eq!(report.support(), 64 + 128 + 24 + 64);
+        assert_eq!(report.total(), 1000 + report.support());
+        assert_eq!(report.inline().total(), report.total() - 64);
+
+        let sum: SpaceReport = [report, report.inline()].into_iter().sum();
+        assert_eq!(sum, report + report.inline());
+        assert_eq!(sum.data, 2000);
+        assert_eq!(sum.overhead, 64);
+        assert_eq!(SpaceReport::default().total(), 0);
+    }
+
+    #[test]
+    fn output() {
+        let report = SpaceReport {
+            data: 300,
+            l0: 100,
+            ..SpaceReport::default()
+        };
+        assert_eq!(
+            report.to_json(),
+            "{\"data\":300,\"l0\":100,\"l12\":0,\"select_samples\":0,\"other\":0,\"padding\":0,\
+             \"overhead\":0,\"slack\":0,\"total\":400}"
+        );
+        let table = report.to_string();
+        assert_eq!(table.lines().count(), 10);
+        assert!(table.contains("data                         300   75.00%"));
+        assert!(table.lines().last().unwrap().starts_with("total"));
+        assert!(SpaceReport::default().to_string().contains("0.00%"));
+    }
+
+    #[test]
+    fn bit_vecs() {
+        let mut bits: BitVec<u64, Lsb0> = BitVec::with_capacity(1000);
+        bits.resize(100, true);
+        let report = bit_vec(&bits);
+        assert_eq!(report.data, 100);
+        assert_eq!(report.padding, 28);
+        assert_eq!(report.total(), bits.capacity());
+        assert_eq!(bit_vec(&BitVec::new()).total(), 0);
+
+        let mut vec: Vec<u32> = Vec::with_capacity(10);
+        vec.push(1);
+        assert_eq!(super::bits(&vec), 32);
+        assert_eq!(slack(&vec), (vec.capacity() - 1) * 32);
+    }
+}
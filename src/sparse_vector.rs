@@ -0,0 +1,315 @@
+//! # SparseVector
+//!
+//! A bit vector which stores the positions of its ones with the Elias-Fano encoding as presented
+//! by [Elias](https://doi.org/10.1145/321812.321820) and Fano.
+//! Each position is split into its lower `log(n / m)` bits, which are stored as they are, and its
+//! upper bits, which are stored in unary as gaps in a bit vector with `m` ones and `n / 2^l` zeros.
+//! With `m` ones in `n` bits the vector needs about `m (2 + log(n / m))` bits, which is far less
+//! than a [`RunawayVector`] for densities below one percent.
+//!
+//! [`AdaptiveVector`] picks the sparse or the dense representation from the density of the ones.
+//!
+use std::mem;
+
+use bitvec::field::BitField;
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+use crate::rank_select::RankSelect;
+use crate::runaway_vector::RunawayVector;
+use crate::space_report::{self, SpaceReport};
+
+/// Vectors with less ones than this are stored as a [`SparseVector`] by [`AdaptiveVector`].
+pub const SPARSE_DENSITY: f64 = 0.01;
+
+pub struct SparseVector {
+    len: usize,
+    ones: usize,
+    low_width: usize,
+    lows: BitVec<u64, Lsb0>,
+    highs: RunawayVector<'static>,
+}
+
+impl SparseVector {
+    pub fn new(bit_vec: &BitVec<u64, Lsb0>) -> Self {
+        Self::from_positions(bit_vec.len(), &bit_vec.iter_ones().collect::<Vec<usize>>())
+    }
+
+    /// Builds the vector of length `len` from the sorted positions of its ones.
+    pub fn from_positions(len: usize, positions: &[usize]) -> Self {
+        assert!(
+            positions.windows(2).all(|pair| pair[0] < pair[1]),
+            "positions are not sorted"
+        );
+        assert!(positions.last().is_none_or(|last| *last < len));
+        let ones = positions.len();
+        let low_width = if ones == 0 || len <= ones {
+            0
+        } else {
+            (usize::BITS - 1 - (len / ones).leading_zeros()) as usize
+        };
+
+        let mut lows = BitVec::with_capacity(ones * low_width);
+        let mut highs = BitVec::repeat(false, ones + (len >> low_width) + 1);
+        for (i, pos) in positions.iter().enumerate() {
+            if low_width > 0 {
+                let start = lows.len();
+                lows.resize(start + low_width, false);
+                lows[start..].store_le(pos & ((1 << low_width) - 1));
+            }
+            highs.set((pos >> low_width) + i, true);
+        }
+
+        SparseVector {
+            len,
+            ones,
+            low_width,
+            lows,
+            highs: RunawayVector::from_owned(highs),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of ones.
+    pub fn ones(&self) -> usize {
+        self.ones
+    }
+
+    pub fn access(&self, idx: usize) -> bool {
+        assert!(idx < self.len);
+        self.rank1(idx + 1) != self.rank1(idx)
+    }
+
+    pub fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank1(idx)
+    }
+
+    /// Counts the ones in front of `idx`. `idx` may be the length of the vector.
+    pub fn rank1(&self, idx: usize) -> usize {
+        assert!(idx <= self.len);
+        let high = idx >> self.low_width;
+        // The ones with a smaller upper part are in front of the zero which ends their bucket.
+        let mut pos = if high == 0 {
+            0
+        } else {
+            self.highs.select0(high).unwrap() + 1
+        };
+        let mut rank = pos - high;
+        let low = idx & ((1 << self.low_width) - 1);
+        while pos < self.highs.len() && self.highs.access(pos) && self.low(rank) < low {
+            pos += 1;
+            rank += 1;
+        }
+        rank
+    }
+
+    pub fn select0(&self, rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.len - self.ones {
+            return None;
+        }
+        // Binary search for the number of ones in front of the zero, which is the first one with
+        // at least `rank` zeros in front of it.
+        let mut l = 0;
+        let mut r = self.ones;
+        while l < r {
+            let m = (l + r) / 2;
+            if self.select1(m + 1).unwrap() - m >= rank {
+                r = m;
+            } else {
+                l = m + 1;
+            }
+        }
+        Some(rank - 1 + l)
+    }
+
+    pub fn select1(&self, rank: usize) -> Option<usize> {
+        assert!(rank > 0);
+        if rank > self.ones {
+            return None;
+        }
+        let high = self.highs.select1(rank).unwrap() - (rank - 1);
+        Some((high << self.low_width) | self.low(rank - 1))
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.lows.len() + self.highs.len() + self.highs.space_usage() + mem::size_of::<Self>()
+    }
+
+    /// The lower and the upper bits are the data, the index of the upper bits is reported as it is.
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport::of::<Self>()
+            + space_report::bit_vec(&self.lows)
+            + self.highs.space_report().inline()
+    }
+
+    fn low(&self, i: usize) -> usize {
+        if self.low_width == 0 {
+            return 0;
+        }
+        self.lows[i * self.low_width..(i + 1) * self.low_width].load_le()
+    }
+}
+
+impl RankSelect for SparseVector {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn access(&self, idx: usize) -> bool {
+        self.access(idx)
+    }
+
+    fn rank0(&self, idx: usize) -> usize {
+        self.rank0(idx)
+    }
+
+    fn rank1(&self, idx: usize) -> usize {
+        self.rank1(idx)
+    }
+
+    fn select0(&self, rank: usize) -> Option<usize> {
+        self.select0(rank)
+    }
+
+    fn select1(&self, rank: usize) -> Option<usize> {
+        self.select1(rank)
+    }
+
+    fn space_usage(&self) -> usize {
+        self.space_usage()
+    }
+
+    fn space_report(&self) -> SpaceReport {
+        self.space_report()
+    }
+}
+
+/// A bit vector which is stored as a [`SparseVector`] if less than [`SPARSE_DENSITY`] of its bits
+/// are ones and as a [`RunawayVector`] otherwise.
+pub enum AdaptiveVector<'a> {
+    Dense(RunawayVector<'a>),
+    Sparse(SparseVector),
+}
+
+impl<'a> AdaptiveVector<'a> {
+    pub fn new(bit_vec: &'a BitVec<u64, Lsb0>) -> Self {
+        let ones = bit_vec.count_ones();
+        if (ones as f64) < bit_vec.len() as f64 * SPARSE_DENSITY {
+            AdaptiveVector::Sparse(SparseVector::new(bit_vec))
+        } else {
+            AdaptiveVector::Dense(RunawayVector::new(bit_vec))
+        }
+    }
+
+    fn inner(&self) -> &dyn RankSelect {
+        match self {
+            AdaptiveVector::Dense(dense) => dense,
+            AdaptiveVector::Sparse(sparse) => sparse,
+        }
+    }
+}
+
+impl RankSelect for AdaptiveVector<'_> {
+    fn len(&self) -> usize {
+        self.inner().len()
+    }
+
+    fn access(&self, idx: usize) -> bool {
+        self.inner().access(idx)
+    }
+
+    fn rank0(&self, idx: usize) -> usize {
+        self.inner().rank0(idx)
+    }
+
+    fn rank1(&self, idx: usize) -> usize {
+        self.inner().rank1(idx)
+    }
+
+    fn select0(&self, rank: usize) -> Option<usize> {
+        self.inner().select0(rank)
+    }
+
+    fn select1(&self, rank: usize) -> Option<usize> {
+        self.inner().select1(rank)
+    }
+
+    fn space_usage(&self) -> usize {
+        // Like for every vector, the borrowed bits of a dense vector are not counted.
+        match self {
+            AdaptiveVector::Dense(dense) => dense.space_usage(),
+            AdaptiveVector::Sparse(sparse) => sparse.space_usage(),
+        }
+    }
+
+    fn space_report(&self) -> SpaceReport {
+        SpaceReport::of::<Self>() + self.inner().space_report().inline()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rank_select::tests::{self, random_bit_vec};
+
+    #[test]
+    fn same_as_runaway() {
+        let cases = [
+            (20_000, 0.001),
+            (5000, 0.01),
+            (3000, 0.5),
+            (1000, 0.0),
+            (700, 1.0),
+        ];
+        for (len, density) in cases {
+            let bv = random_bit_vec(len, density, len as u64);
+            tests::same_as_runaway(&SparseVector::new(&bv), &bv);
+        }
+    }
+
+    #[test]
+    fn adaptive() {
+        let sparse = random_bit_vec(50_000, 0.002, 1);
+        let dense = random_bit_vec(50_000, 0.3, 2);
+        let adaptive_sparse = AdaptiveVector::new(&sparse);
+        let adaptive_dense = AdaptiveVector::new(&dense);
+        assert!(matches!(adaptive_sparse, AdaptiveVector::Sparse(_)));
+        assert!(matches!(adaptive_dense, AdaptiveVector::Dense(_)));
+        // The sparse representation replaces the bits, which a dense vector needs beside its index.
+        let runaway = RunawayVector::new(&sparse);
+        assert!(adaptive_sparse.space_usage() < sparse.len() + runaway.space_usage());
+        assert_eq!(
+            adaptive_dense.space_usage(),
+            RunawayVector::new(&dense).space_usage()
+        );
+        assert_eq!(adaptive_sparse.rank1(40_000), sparse[..40_000].count_ones());
+        assert_eq!(adaptive_dense.select1(3), dense.iter_ones().nth(2));
+    }
+
+    #[test]
+    fn space_report() {
+        let bit_vec = random_bit_vec(100_000, 0.001, 47);
+        let sparse = SparseVector::new(&bit_vec);
+        let report = sparse.space_report();
+        let highs = sparse.highs.space_report();
+        assert_eq!(report.data, sparse.lows.len() + sparse.highs.len());
+        assert_eq!(report.l12, highs.l12);
+        assert_eq!(report.overhead, 8 * mem::size_of::<SparseVector>());
+        assert!(report.total() < bit_vec.len() / 10);
+
+        let dense = random_bit_vec(10_000, 0.5, 48);
+        let adaptive = AdaptiveVector::new(&dense);
+        assert_eq!(
+            adaptive.space_report().inline(),
+            RunawayVector::new(&dense).space_report().inline()
+        );
+    }
+}
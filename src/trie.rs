@@ -0,0 +1,428 @@
+//! # Trie
+//!
+//! A static string dictionary stored as a [`Louds`] trie. Each node except the root has a byte
+//! label, which is kept in an array indexed by the breadth first node ids. Because the children of
+//! a node have consecutive ids and are sorted by their label, a child is found with a binary search
+//! over a slice of the labels.
+//!
+//! A second bit vector marks the nodes which end a key. The id of a key is the rank of its node in
+//! this bit vector, so the ids go from `0` to the number of keys.
+//!
+//! A trie which was written with [`Trie::write_to`] can be borrowed from a file mapped with
+//! [`serialize::map`] by [`Trie::from_bytes`], which keeps the bits and labels in the mapping and
+//! only builds the rank and select indices.
+//!
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::{fs, io, mem};
+
+use bitvec::order::Lsb0;
+use bitvec::prelude::{BitSlice, BitVec};
+
+use crate::louds::Louds;
+use crate::runaway_vector::RunawayVector;
+use crate::serialize;
+use crate::space_report::{self, SpaceReport};
+
+pub struct Trie<'a> {
+    louds: Louds<'a>,
+    // The label of each node, where the label of the root is unused.
+    labels: Cow<'a, [u8]>,
+    terminals: RunawayVector<'a>,
+}
+
+impl<'a> Trie<'a> {
+    /// Builds the trie of `keys`. Duplicate keys are stored once.
+    pub fn new<K: AsRef<[u8]>>(keys: &[K]) -> Trie<'static> {
+        let mut keys: Vec<&[u8]> = keys.iter().map(|key| key.as_ref()).collect();
+        keys.sort_unstable();
+        keys.dedup();
+
+        let mut degrees = Vec::new();
+        let mut labels = vec![0];
+        let mut terminals: BitVec<u64, Lsb0> = BitVec::new();
+        // Each node is the range of keys which share its prefix of length `depth`.
+        let mut queue = VecDeque::new();
+        if !keys.is_empty() {
+            queue.push_back((0, keys.len(), 0));
+        }
+        while let Some((mut start, end, depth)) = queue.pop_front() {
+            // As the keys are sorted, a key which ends at this node comes first.
+            let is_terminal = keys[start].len() == depth;
+            terminals.push(is_terminal);
+            if is_terminal {
+                start += 1;
+            }
+            let mut degree = 0;
+            while start < end {
+                let label = keys[start][depth];
+                let child_end = start + keys[start..end].partition_point(|key| key[depth] == label);
+                labels.push(label);
+                queue.push_back((start, child_end, depth + 1));
+                degree += 1;
+                start = child_end;
+            }
+            degrees.push(degree);
+        }
+
+        Trie {
+            louds: Louds::from_degrees(&degrees),
+            labels: Cow::Owned(labels),
+            terminals: RunawayVector::from_owned(terminals),
+        }
+    }
+
+    /// The number of keys.
+    pub fn len(&self) -> usize {
+        self.terminals.rank1(self.terminals.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Finds the id of `key`.
+    pub fn lookup(&self, key: &[u8]) -> Option<usize> {
+        let node = self.find(key)?;
+        self.id(node)
+    }
+
+    /// Finds the key with `id`.
+    pub fn reverse(&self, id: usize) -> Option<Vec<u8>> {
+        if id >= self.len() {
+            return None;
+        }
+        let mut node = self.terminals.select1(id + 1)?;
+        let mut key = Vec::new();
+        while let Some(parent) = self.louds.parent(node) {
+            key.push(self.labels[node]);
+            node = parent;
+        }
+        key.reverse();
+        Some(key)
+    }
+
+    /// Iterates over the ids and keys which start with `prefix` in lexicographic order.
+    pub fn predictive_search(&self, prefix: &[u8]) -> PredictiveSearch<'_> {
+        PredictiveSearch {
+            trie: self,
+            stack: self
+                .find(prefix)
+                .map(|node| (node, prefix.len()))
+                .into_iter()
+                .collect(),
+            key: prefix.to_vec(),
+            prefix_len: prefix.len(),
+        }
+    }
+
+    /// Iterates over the ids and lengths of the keys which are a prefix of `text`, shortest first.
+    pub fn common_prefix_search<'b>(&'b self, text: &'b [u8]) -> CommonPrefixSearch<'b> {
+        CommonPrefixSearch {
+            trie: self,
+            text,
+            node: Some(0).filter(|_| !self.louds.is_empty()),
+            depth: 0,
+        }
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.louds.space_usage()
+            + self.labels.len() * 8
+            + self.terminals.len()
+            + self.terminals.space_usage()
+            + mem::size_of::<Self>()
+    }
+
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            data: space_report::bits(&self.labels),
+            slack: match &self.labels {
+                Cow::Borrowed(_) => 0,
+                Cow::Owned(labels) => space_report::slack(labels),
+            },
+            ..SpaceReport::of::<Self>()
+        } + self.louds.space_report().inline()
+            + self.terminals.space_report().inline()
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_bit_vec(writer, self.louds.bits().bit_vec())?;
+        serialize::write_bytes(writer, &self.labels)?;
+        serialize::write_bit_vec(writer, self.terminals.bit_vec())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Trie<'static>> {
+        let louds = serialize::read_bit_vec(reader)?;
+        let labels = serialize::read_bytes(reader)?;
+        let terminals = serialize::read_bit_vec(reader)?;
+        check(&louds, &labels, terminals.len())?;
+        Ok(Trie {
+            louds: Louds::new(louds),
+            labels: Cow::Owned(labels),
+            terminals: RunawayVector::from_owned(terminals),
+        })
+    }
+
+    /// Borrows a trie written by [`Trie::write_to`] from `bytes`, like the mapping of a file. The
+    /// bits and labels stay in `bytes` unless their words do not start at a multiple of 8 bytes in
+    /// memory, which a mapping always does.
+    pub fn from_bytes(mut bytes: &'a [u8]) -> io::Result<Self> {
+        let (louds, louds_len) = serialize::borrow_bit_vec(&mut bytes)?;
+        let labels = serialize::borrow_bytes(&mut bytes)?;
+        let (terminals, terminals_len) = serialize::borrow_bit_vec(&mut bytes)?;
+        check(
+            &BitSlice::from_slice(&louds)[..louds_len],
+            labels,
+            terminals_len,
+        )?;
+        Ok(Trie {
+            louds: Louds::from_words(louds, louds_len),
+            labels: Cow::Borrowed(labels),
+            terminals: RunawayVector::from_words(terminals, terminals_len),
+        })
+    }
+
+    /// Loads a trie written by [`Trie::write_to`] from a file. The trie owns its bits and labels,
+    /// so the file is read at once. To keep them in the file instead, map it with
+    /// [`serialize::map`] and borrow the trie with [`Trie::from_bytes`].
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Trie<'static>> {
+        let bytes = fs::read(path)?;
+        Self::read_from(&mut &bytes[..])
+    }
+
+    fn find(&self, key: &[u8]) -> Option<usize> {
+        if self.louds.is_empty() {
+            return None;
+        }
+        key.iter()
+            .try_fold(0, |node, label| self.child(node, *label))
+    }
+
+    fn child(&self, node: usize, label: u8) -> Option<usize> {
+        let children = self.louds.children(node);
+        self.labels[children.clone()]
+            .binary_search(&label)
+            .ok()
+            .map(|i| children.start + i)
+    }
+
+    fn id(&self, node: usize) -> Option<usize> {
+        Some(self.terminals.rank1(node)).filter(|_| self.terminals.access(node))
+    }
+}
+
+// Checks that the parts which were read form a trie, so that a corrupt file is rejected instead of
+// panicking on a later query.
+fn check(louds: &BitSlice<u64, Lsb0>, labels: &[u8], terminals: usize) -> io::Result<()> {
+    let nodes = louds.len() / 2;
+    if !Louds::is_valid(louds) || labels.len() != nodes.max(1) || terminals != nodes {
+        return Err(serialize::invalid_data("inconsistent trie"));
+    }
+    Ok(())
+}
+
+pub struct PredictiveSearch<'a> {
+    trie: &'a Trie<'a>,
+    // The nodes which are left to visit and the length of their key.
+    stack: Vec<(usize, usize)>,
+    key: Vec<u8>,
+    prefix_len: usize,
+}
+
+impl Iterator for PredictiveSearch<'_> {
+    type Item = (usize, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, len)) = self.stack.pop() {
+            // The node of the prefix itself has no label of its own in the key.
+            if len > self.prefix_len {
+                self.key.truncate(len - 1);
+                self.key.push(self.trie.labels[node]);
+            }
+            self.stack.extend(
+                self.trie
+                    .louds
+                    .children(node)
+                    .rev()
+                    .map(|child| (child, len + 1)),
+            );
+            if let Some(id) = self.trie.id(node) {
+                return Some((id, self.key.clone()));
+            }
+        }
+        None
+    }
+}
+
+pub struct CommonPrefixSearch<'a> {
+    trie: &'a Trie<'a>,
+    text: &'a [u8],
+    node: Option<usize>,
+    depth: usize,
+}
+
+impl Iterator for CommonPrefixSearch<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.node {
+            let depth = self.depth;
+            self.node = self
+                .text
+                .get(depth)
+                .and_then(|label| self.trie.child(node, *label));
+            self.depth += 1;
+            if let Some(id) = self.trie.id(node) {
+                return Some((id, depth));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    const KEYS: [&str; 9] = [
+        "a",
+        "app",
+        "apple",
+        "application",
+        "apply",
+        "banana",
+        "band",
+        "bandana",
+        "can",
+    ];
+
+    #[test]
+    fn lookup_and_reverse() {
+        let trie = Trie::new(&KEYS);
+        assert_eq!(trie.len(), KEYS.len());
+        let mut ids: Vec<usize> = KEYS
+            .iter()
+            .map(|key| trie.lookup(key.as_bytes()).unwrap())
+            .collect();
+        for (key, id) in KEYS.iter().zip(ids.iter()) {
+            assert_eq!(trie.reverse(*id).unwrap(), key.as_bytes());
+        }
+        ids.sort_unstable();
+        assert_eq!(ids, (0..KEYS.len()).collect::<Vec<usize>>());
+        assert_eq!(trie.lookup(b"ap"), None);
+        assert_eq!(trie.lookup(b"applications"), None);
+        assert_eq!(trie.lookup(b""), None);
+        assert_eq!(trie.reverse(KEYS.len()), None);
+    }
+
+    #[test]
+    fn predictive_search() {
+        let trie = Trie::new(&KEYS);
+        let keys: Vec<Vec<u8>> = trie.predictive_search(b"app").map(|(_, key)| key).collect();
+        assert_eq!(keys, vec![&b"app"[..], b"apple", b"application", b"apply"]);
+        let keys: Vec<Vec<u8>> = trie.predictive_search(b"").map(|(_, key)| key).collect();
+        assert_eq!(
+            keys,
+            KEYS.iter()
+                .map(|key| key.as_bytes())
+                .collect::<Vec<&[u8]>>()
+        );
+        for (id, key) in trie.predictive_search(b"ban") {
+            assert_eq!(trie.lookup(&key), Some(id));
+        }
+        assert_eq!(trie.predictive_search(b"x").count(), 0);
+    }
+
+    #[test]
+    fn common_prefix_search() {
+        let trie = Trie::new(&KEYS);
+        let lens: Vec<usize> = trie
+            .common_prefix_search(b"applesauce")
+            .map(|(_, len)| len)
+            .collect();
+        assert_eq!(lens, vec![1, 3, 5]);
+        let (id, len) = trie.common_prefix_search(b"bandanas").last().unwrap();
+        assert_eq!((trie.reverse(id).unwrap(), len), (b"bandana".to_vec(), 7));
+        assert_eq!(trie.common_prefix_search(b"zebra").count(), 0);
+    }
+
+    #[test]
+    fn empty_key_and_empty_trie() {
+        let trie = Trie::new(&["", "x"]);
+        assert_eq!(trie.lookup(b""), Some(0));
+        assert_eq!(trie.common_prefix_search(b"xy").count(), 2);
+        let empty = Trie::new::<&str>(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.lookup(b"a"), None);
+        assert_eq!(empty.predictive_search(b"").count(), 0);
+    }
+
+    #[test]
+    fn corrupt() {
+        let trie = Trie::new(&KEYS);
+        let mut bytes = Vec::new();
+        trie.write_to(&mut bytes).unwrap();
+        for len in 0..bytes.len() {
+            assert!(Trie::read_from(&mut &bytes[..len]).is_err());
+            assert!(Trie::from_bytes(&bytes[..len]).is_err());
+        }
+        // Altered bytes are either rejected or give a trie which can be searched without panics.
+        for i in 0..bytes.len() {
+            let mut altered = bytes.clone();
+            altered[i] ^= 0x55;
+            assert_eq!(
+                Trie::read_from(&mut &altered[..]).is_ok(),
+                Trie::from_bytes(&altered).is_ok()
+            );
+            if let Ok(altered) = Trie::read_from(&mut &altered[..]) {
+                for key in KEYS {
+                    altered.lookup(key.as_bytes());
+                    altered.predictive_search(key.as_bytes()).count();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn load() {
+        let trie = Trie::new(&KEYS);
+        let path = std::env::temp_dir().join(format!("trie-{}.bin", std::process::id()));
+        trie.write_to(&mut File::create(&path).unwrap()).unwrap();
+        let loaded = Trie::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        for key in KEYS {
+            assert_eq!(loaded.lookup(key.as_bytes()), trie.lookup(key.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn mapped() {
+        let trie = Trie::new(&KEYS);
+        let path = std::env::temp_dir().join(format!("trie-mapped-{}.bin", std::process::id()));
+        trie.write_to(&mut File::create(&path).unwrap()).unwrap();
+        // Safety: The file is only removed after the trie is dropped.
+        let mmap = unsafe { serialize::map(&path).unwrap() };
+        let mapped = Trie::from_bytes(&mmap).unwrap();
+        for key in KEYS {
+            assert_eq!(mapped.lookup(key.as_bytes()), trie.lookup(key.as_bytes()));
+        }
+        assert_eq!(mapped.common_prefix_search(b"applesauce").count(), 3);
+        // The bits and labels are read from the mapping rather than copied.
+        let range = mmap.as_ptr_range();
+        for data in [
+            mapped.louds.bits().words().as_ptr() as *const u8,
+            mapped.labels.as_ptr(),
+            mapped.terminals.words().as_ptr() as *const u8,
+        ] {
+            assert!(range.contains(&data));
+        }
+        assert_eq!(mapped.space_report().slack, 0);
+        drop(mapped);
+        drop(mmap);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+//! # WaveletMatrix
+//!
+//! A wavelet matrix over a sequence of bytes as presented by
+//! [Claude et al.](https://doi.org/10.1016/j.is.2014.06.002) Each level stores one bit of every
+//! symbol in a [`RunawayVector`], starting with the most significant bit. After each level the
+//! symbols are stably partitioned by their bit at this level, the zeros first.
+//!
+//! Access, rank and select need one rank or select query per level and therefore run in
+//! `O(log σ)`.
+//!
+use std::{cmp, mem};
+
+use bitvec::prelude::BitVec;
+
+use crate::runaway_vector::RunawayVector;
+use crate::space_report::{self, SpaceReport};
+
+pub struct WaveletMatrix {
+    levels: Vec<RunawayVector<'static>>,
+    // The number of zeros on each level which is also the start of the ones on the next level.
+    zeros: Vec<usize>,
+    len: usize,
+}
+
+impl WaveletMatrix {
+    pub fn new(symbols: &[u8]) -> Self {
+        // Only use as many levels as needed for the largest symbol, but always at least one.
+        let max = symbols.iter().copied().max().unwrap_or(0);
+        let level_count = cmp::max(u8::BITS - max.leading_zeros(), 1) as usize;
+
+        let mut levels = Vec::with_capacity(level_count);
+        let mut zeros = Vec::with_capacity(level_count);
+        let mut current = symbols.to_vec();
+        let mut next_zeros = Vec::with_capacity(symbols.len());
+        let mut next_ones = Vec::with_capacity(symbols.len());
+        for level in 0..level_count {
+            let shift = level_count - level - 1;
+            let mut bit_vec = BitVec::with_capacity(symbols.len());
+            for symbol in current.iter() {
+                let bit = (symbol >> shift) & 1 == 1;
+                bit_vec.push(bit);
+                if bit {
+                    next_ones.push(*symbol);
+                } else {
+                    next_zeros.push(*symbol);
+                }
+            }
+            zeros.push(next_zeros.len());
+            levels.push(RunawayVector::from_owned(bit_vec));
+            current.clear();
+            current.append(&mut next_zeros);
+            current.append(&mut next_ones);
+        }
+
+        WaveletMatrix {
+            levels,
+            zeros,
+            len: symbols.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn access(&self, mut idx: usize) -> u8 {
+        assert!(idx < self.len);
+        let mut symbol = 0;
+        for (level, bits) in self.levels.iter().enumerate() {
+            symbol <<= 1;
+            if bits.access(idx) {
+                symbol |= 1;
+                idx = self.zeros[level] + bits.rank1(idx);
+            } else {
+                idx = bits.rank0(idx);
+            }
+        }
+        symbol
+    }
+
+    /// Counts the occurrences of `symbol` in front of `idx`. `idx` may be the length of the
+    /// sequence.
+    pub fn rank(&self, symbol: u8, idx: usize) -> usize {
+        assert!(idx <= self.len);
+        if !self.fits(symbol) {
+            return 0;
+        }
+        let mut start = 0;
+        let mut end = idx;
+        for level in 0..self.levels.len() {
+            (start, end) = (
+                self.step(symbol, level, start),
+                self.step(symbol, level, end),
+            );
+        }
+        end - start
+    }
+
+    /// Finds the position of the `nth` occurrence of `symbol`, starting with 1.
+    pub fn select(&self, symbol: u8, nth: usize) -> Option<usize> {
+        assert!(nth > 0);
+        if nth > self.rank(symbol, self.len) {
+            return None;
+        }
+        // Find the start of the symbol on the last level and walk the levels back up.
+        let mut start = 0;
+        for level in 0..self.levels.len() {
+            start = self.step(symbol, level, start);
+        }
+        let mut pos = start + nth - 1;
+        for (level, bits) in self.levels.iter().enumerate().rev() {
+            pos = if self.bit(symbol, level) {
+                bits.select1(pos - self.zeros[level] + 1)?
+            } else {
+                bits.select0(pos + 1)?
+            };
+        }
+        Some(pos)
+    }
+
+    pub fn space_usage(&self) -> usize {
+        self.levels
+            .iter()
+            .map(|level| level.len() + level.space_usage())
+            .sum::<usize>()
+            + self.zeros.len() * 64
+            + mem::size_of::<Self>()
+    }
+
+    /// The levels are the data, the zeros of each level are other support.
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport {
+            other: space_report::bits(&self.zeros),
+            slack: space_report::slack(&self.levels) + space_report::slack(&self.zeros),
+            ..SpaceReport::of::<Self>()
+        } + self.levels.iter().map(RunawayVector::space_report).sum()
+    }
+
+    // Maps a position on `level` to the position on the next level, following the bit of `symbol`.
+    fn step(&self, symbol: u8, level: usize, idx: usize) -> usize {
+        if self.bit(symbol, level) {
+            self.zeros[level] + self.levels[level].rank1(idx)
+        } else {
+            self.levels[level].rank0(idx)
+        }
+    }
+
+    fn bit(&self, symbol: u8, level: usize) -> bool {
+        (symbol >> (self.levels.len() - level - 1)) & 1 == 1
+    }
+
+    fn fits(&self, symbol: u8) -> bool {
+        self.levels.len() >= 8 || symbol >> self.levels.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"mississippi river banks";
+
+    #[test]
+    fn access() {
+        let matrix = WaveletMatrix::new(TEXT);
+        assert_eq!(matrix.len(), TEXT.len());
+        for (i, symbol) in TEXT.iter().enumerate() {
+            assert_eq!(matrix.access(i), *symbol);
+        }
+    }
+
+    #[test]
+    fn rank_and_select() {
+        let matrix = WaveletMatrix::new(TEXT);
+        for symbol in [b'i', b's', b'p', b' ', b'k', b'z', 0, 255] {
+            let mut count = 0;
+            for (i, c) in TEXT.iter().enumerate() {
+                assert_eq!(matrix.rank(symbol, i), count);
+                if *c == symbol {
+                    count += 1;
+                    assert_eq!(matrix.select(symbol, count), Some(i));
+                }
+            }
+            assert_eq!(matrix.rank(symbol, TEXT.len()), count);
+            assert_eq!(matrix.select(symbol, count + 1), None);
+        }
+    }
+
+    #[test]
+    fn small_alphabet() {
+        let symbols = [0, 1, 1, 0, 1, 0, 0];
+        let matrix = WaveletMatrix::new(&symbols);
+        assert_eq!(matrix.levels.len(), 1);
+        assert_eq!(matrix.rank(1, symbols.len()), 3);
+        assert_eq!(matrix.rank(2, symbols.len()), 0);
+        assert_eq!(matrix.select(0, 4), Some(6));
+    }
+}
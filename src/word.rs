@@ -0,0 +1,46 @@
+//! # Word
+//!
+//! Broadword helpers on single 64 bit words, which are shared by the bit vectors of this crate.
+//!
+//! The vectors read the raw words of a `BitVec`, so its first bit must be the lowest bit of its
+//! first word. A `BitVec` which was cut from another one can start inside its first word, which
+//! [`is_aligned`] detects.
+//!
+use bitvec::order::Lsb0;
+use bitvec::prelude::BitVec;
+
+/// Whether the bits start at the lowest bit of the first raw word.
+pub(crate) fn is_aligned(bit_vec: &BitVec<u64, Lsb0>) -> bool {
+    bit_vec.as_bitptr().bit().into_inner() == 0
+}
+
+// The position of the `rank`-th one in `word`, starting with 1.
+pub(crate) fn select_in_word(mut word: u64, rank: usize) -> usize {
+    for _ in 1..rank {
+        word &= word - 1;
+    }
+    word.trailing_zeros() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select() {
+        let word = 0b1011_0010_u64 | 1 << 63;
+        assert_eq!(select_in_word(word, 1), 1);
+        assert_eq!(select_in_word(word, 2), 4);
+        assert_eq!(select_in_word(word, 4), 7);
+        assert_eq!(select_in_word(word, 5), 63);
+        assert_eq!(select_in_word(u64::MAX, 64), 63);
+    }
+
+    #[test]
+    fn aligned() {
+        let bit_vec: BitVec<u64, Lsb0> = BitVec::repeat(true, 100);
+        assert!(is_aligned(&bit_vec));
+        assert!(!is_aligned(&bit_vec[5..].to_bitvec()));
+        assert!(is_aligned(&BitVec::new()));
+    }
+}